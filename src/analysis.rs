@@ -0,0 +1,429 @@
+//! Finds out how much of the draw can be deduced from the exclusions alone.
+//!
+//! Anyone who knows the exclusions knows the bipartite graph of allowed (sender, recipient) pairs,
+//! and every valid assignment is a perfect matching in it. A pair that appears in every perfect matching
+//! is forced, so its pairing is known before the draw even happens. A pair that appears in no perfect matching
+//! can never be drawn, which shrinks the choices of everyone involved.
+//!
+//! Both are found with the Dulmage–Mendelsohn decomposition: take any perfect matching, point matched edges
+//! from recipient to sender and every other allowed edge from sender to recipient. An allowed pair is part of
+//! some perfect matching exactly if it is matched or both of its ends lie in the same strongly connected component,
+//! because those are the pairs on an alternating cycle.
+//!
+//! When there is no valid assignment at all, Hall's theorem says there is a group of senders who together
+//! can send to fewer recipients than there are people in the group. `hall_violation` finds one.
+
+use std::{collections::HashMap, fmt::Write, rc::Rc};
+
+use petgraph::{algo::tarjan_scc, graph::DiGraph};
+use serde::Serialize;
+
+use crate::{
+    configuration::{Configuration, Participant},
+    counting, flow_network,
+};
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Pair {
+    pub sender: String,
+    pub recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PossibleCount {
+    pub name: String,
+    /// Recipients this participant has in at least one valid assignment
+    pub possible_recipients: usize,
+    /// Senders this participant has in at least one valid assignment
+    pub possible_senders: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeducibilityReport {
+    pub participant_count: usize,
+    pub feasible: bool,
+    pub infeasibility_reason: Option<String>,
+    /// Pairs that are part of every valid assignment
+    pub forced_pairs: Vec<Pair>,
+    /// Pairs the exclusions allow, but that are part of no valid assignment
+    pub impossible_pairs: Vec<Pair>,
+    /// Participants with at most the threshold of possible recipients or senders
+    pub low_entropy: Vec<PossibleCount>,
+}
+
+impl DeducibilityReport {
+    pub fn has_warnings(&self) -> bool {
+        !self.forced_pairs.is_empty() || !self.low_entropy.is_empty()
+    }
+
+    /// The full report, including the pairings that can be deduced. Only meant for the organizer's eyes.
+    pub fn to_human(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "{} participants", self.participant_count).unwrap();
+
+        if let Some(reason) = &self.infeasibility_reason {
+            writeln!(report, "ERROR: {}", reason).unwrap();
+            return report;
+        }
+
+        for pair in self.forced_pairs.iter() {
+            writeln!(
+                report,
+                "WARNING: {:?} always sends to {:?}, anyone who knows the exclusions can deduce it",
+                pair.sender, pair.recipient
+            )
+            .unwrap();
+        }
+        for count in self.low_entropy.iter() {
+            writeln!(
+                report,
+                "WARNING: {:?} can end up with only {} possible recipient(s) and {} possible sender(s)",
+                count.name, count.possible_recipients, count.possible_senders
+            )
+            .unwrap();
+        }
+        for pair in self.impossible_pairs.iter() {
+            writeln!(
+                report,
+                "NOTE: {:?} is allowed to send to {:?}, but no valid assignment contains that pair",
+                pair.sender, pair.recipient
+            )
+            .unwrap();
+        }
+
+        if !self.has_warnings() {
+            writeln!(report, "No pairing can be deduced from the exclusions.").unwrap();
+        }
+        report
+    }
+
+    /// Warnings that are safe to print during a draw: they name who is affected but never their recipient.
+    pub fn to_anonymous_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.forced_pairs.is_empty() {
+            let mut senders: Vec<&str> = self
+                .forced_pairs
+                .iter()
+                .map(|pair| pair.sender.as_str())
+                .collect();
+            senders.sort();
+            warnings.push(format!(
+                "WARNING: the exclusions leave only one possible recipient for {}. Anyone who knows the exclusions can deduce their pairing.",
+                senders.join(", ")
+            ));
+        }
+        let low_entropy: Vec<&str> = self
+            .low_entropy
+            .iter()
+            .filter(|count| {
+                !self
+                    .forced_pairs
+                    .iter()
+                    .any(|pair| pair.sender == count.name)
+            })
+            .map(|count| count.name.as_str())
+            .collect();
+        if !low_entropy.is_empty() {
+            warnings.push(format!(
+                "WARNING: the exclusions leave very few possible recipients or senders for {}.",
+                low_entropy.join(", ")
+            ));
+        }
+        if !warnings.is_empty() {
+            warnings.push(
+                "Run `secret_santa analyze` to see the details (it shows the deducible pairings!)."
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+}
+
+/// Senders who can only send to fewer recipients than there are senders, so they can't all be given one.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct HallViolation {
+    pub senders: Vec<String>,
+    /// Everyone the senders are allowed to send to
+    pub recipients: Vec<String>,
+}
+
+/// Finds a set of senders violating Hall's condition, or `None` if there is a valid assignment.
+pub fn hall_violation(configuration: &Configuration) -> Option<HallViolation> {
+    let participants = configuration.sorted_participants();
+    let allowed = counting::allowed_matrix(configuration);
+    let n = participants.len();
+
+    // Maximum matching with augmenting paths (Kuhn's algorithm)
+    fn augment(
+        sender: usize,
+        allowed: &[Vec<bool>],
+        visited: &mut [bool],
+        sender_of: &mut [Option<usize>],
+    ) -> bool {
+        for recipient in 0..allowed.len() {
+            if !allowed[sender][recipient] || visited[recipient] {
+                continue;
+            }
+            visited[recipient] = true;
+            if sender_of[recipient].is_none_or(|other| augment(other, allowed, visited, sender_of))
+            {
+                sender_of[recipient] = Some(sender);
+                return true;
+            }
+        }
+        false
+    }
+    let mut sender_of = vec![None; n];
+    let unmatched =
+        (0..n).find(|&sender| !augment(sender, &allowed, &mut vec![false; n], &mut sender_of))?;
+
+    // Everyone reachable from an unmatched sender by alternating paths. Every recipient found is matched,
+    // otherwise the matching wasn't maximum, so there is exactly one more sender than recipients.
+    let mut senders = vec![unmatched];
+    let mut recipients = Vec::new();
+    let mut next = 0;
+    while next < senders.len() {
+        let sender = senders[next];
+        next += 1;
+        for recipient in 0..n {
+            if allowed[sender][recipient] && !recipients.contains(&recipient) {
+                recipients.push(recipient);
+                senders.extend(sender_of[recipient]);
+            }
+        }
+    }
+
+    let names = |mut indices: Vec<usize>| {
+        indices.sort();
+        indices
+            .into_iter()
+            .map(|index| participants[index].name.clone())
+            .collect()
+    };
+    Some(HallViolation {
+        senders: names(senders),
+        recipients: names(recipients),
+    })
+}
+
+/// Analyzes the configuration. Participants with at most `low_entropy_threshold`
+/// possible recipients or senders are reported.
+pub fn analyze(configuration: &Configuration, low_entropy_threshold: usize) -> DeducibilityReport {
+    let participants = configuration.sorted_participants();
+    let participant_count = participants.len();
+
+    let matching =
+        match flow_network::try_generate_assignments(configuration, false, &mut rand::thread_rng())
+        {
+            Ok(matching) => matching,
+            Err(reason) => {
+                return DeducibilityReport {
+                    participant_count,
+                    feasible: false,
+                    infeasibility_reason: Some(reason),
+                    forced_pairs: Vec::new(),
+                    impossible_pairs: Vec::new(),
+                    low_entropy: Vec::new(),
+                }
+            }
+        };
+    let matched: HashMap<Rc<Participant>, Rc<Participant>> = matching
+        .into_iter()
+        .map(|assignment| (assignment.sender, assignment.recipient))
+        .collect();
+
+    let allowed_pairs = flow_network::construct_flow_network(
+        &configuration.participants,
+        &configuration.cannot_send_to,
+        &configuration.cannot_receive_from,
+    )
+    .allowed_pairs();
+
+    // Senders are nodes 0..n, recipients are nodes n..2n
+    let index_of: HashMap<&Rc<Participant>, usize> = participants
+        .iter()
+        .enumerate()
+        .map(|(index, participant)| (participant, index))
+        .collect();
+    let mut graph = DiGraph::<(), ()>::new();
+    let nodes: Vec<_> = (0..2 * participant_count)
+        .map(|_| graph.add_node(()))
+        .collect();
+    for (sender, recipient) in allowed_pairs.iter() {
+        let sender_node = nodes[index_of[sender]];
+        let recipient_node = nodes[participant_count + index_of[recipient]];
+        if matched[sender] == *recipient {
+            graph.add_edge(recipient_node, sender_node, ());
+        } else {
+            graph.add_edge(sender_node, recipient_node, ());
+        }
+    }
+
+    let mut component_of = vec![0; 2 * participant_count];
+    for (component, members) in tarjan_scc(&graph).into_iter().enumerate() {
+        for node in members {
+            component_of[node.index()] = component;
+        }
+    }
+
+    let mut forced_pairs = Vec::new();
+    let mut impossible_pairs = Vec::new();
+    let mut possible_recipients = vec![0; participant_count];
+    let mut possible_senders = vec![0; participant_count];
+    for (sender, recipient) in allowed_pairs.iter() {
+        let same_component =
+            component_of[index_of[sender]] == component_of[participant_count + index_of[recipient]];
+        let pair = Pair {
+            sender: sender.name.clone(),
+            recipient: recipient.name.clone(),
+        };
+        if matched[sender] == *recipient {
+            if !same_component {
+                forced_pairs.push(pair);
+            }
+        } else if !same_component {
+            impossible_pairs.push(pair);
+            continue;
+        }
+        possible_recipients[index_of[sender]] += 1;
+        possible_senders[index_of[recipient]] += 1;
+    }
+
+    let low_entropy = participants
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| {
+            possible_recipients[index] <= low_entropy_threshold
+                || possible_senders[index] <= low_entropy_threshold
+        })
+        .map(|(index, participant)| PossibleCount {
+            name: participant.name.clone(),
+            possible_recipients: possible_recipients[index],
+            possible_senders: possible_senders[index],
+        })
+        .collect();
+
+    DeducibilityReport {
+        participant_count,
+        feasible: true,
+        infeasibility_reason: None,
+        forced_pairs,
+        impossible_pairs,
+        low_entropy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::test_support;
+
+    #[test]
+    fn test_no_exclusions_nothing_forced() {
+        let configuration = test_support::configuration(&["Alice", "Bob", "Charlie", "David"], &[]);
+
+        let report = analyze(&configuration, 1);
+
+        assert!(report.feasible);
+        assert!(report.forced_pairs.is_empty());
+        assert!(report.impossible_pairs.is_empty());
+        assert!(!report.has_warnings());
+    }
+
+    #[test]
+    fn test_single_assignment_is_fully_forced() {
+        // Alice can't send to Bob, so the only valid assignment is Alice -> Charlie -> Bob -> Alice
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie"], &[("Alice", "Bob")]);
+
+        let report = analyze(&configuration, 1);
+
+        assert_eq!(report.forced_pairs.len(), 3);
+        assert!(report.forced_pairs.contains(&Pair {
+            sender: "Alice".to_string(),
+            recipient: "Charlie".to_string()
+        }));
+        assert_eq!(
+            report.impossible_pairs,
+            vec![
+                Pair {
+                    sender: "Bob".to_string(),
+                    recipient: "Charlie".to_string()
+                },
+                Pair {
+                    sender: "Charlie".to_string(),
+                    recipient: "Alice".to_string()
+                },
+            ]
+        );
+        assert_eq!(report.low_entropy.len(), 3);
+    }
+
+    #[test]
+    fn test_partially_forced() {
+        // Alice can only send to Bob, everyone else is free
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David"],
+            &[("Alice", "Charlie"), ("Alice", "David")],
+        );
+
+        let report = analyze(&configuration, 1);
+
+        assert_eq!(
+            report.forced_pairs,
+            vec![Pair {
+                sender: "Alice".to_string(),
+                recipient: "Bob".to_string()
+            }]
+        );
+        // Bob can only ever receive from Alice
+        assert_eq!(
+            report
+                .low_entropy
+                .iter()
+                .map(|count| count.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Alice", "Bob"]
+        );
+        assert_eq!(report.to_anonymous_warnings().len(), 3);
+        assert!(!report.to_anonymous_warnings()[0].contains("Bob"));
+    }
+
+    #[test]
+    fn test_infeasible() {
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie"],
+            &[("Alice", "Bob"), ("Alice", "Charlie")],
+        );
+
+        let report = analyze(&configuration, 1);
+
+        assert!(!report.feasible);
+        assert!(report.infeasibility_reason.is_some());
+    }
+
+    #[test]
+    fn test_hall_violation() {
+        let feasible = test_support::configuration(&["Alice", "Bob", "Charlie"], &[]);
+        assert_eq!(hall_violation(&feasible), None);
+
+        // Alice and Bob can only send to Charlie
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David"],
+            &[
+                ("Alice", "Bob"),
+                ("Alice", "David"),
+                ("Bob", "Alice"),
+                ("Bob", "David"),
+            ],
+        );
+
+        assert_eq!(
+            hall_violation(&configuration),
+            Some(HallViolation {
+                senders: vec!["Alice".to_string(), "Bob".to_string()],
+                recipients: vec!["Charlie".to_string()],
+            })
+        );
+    }
+}
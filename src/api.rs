@@ -0,0 +1,333 @@
+//! A JSON HTTP API around the matcher, for bots and other programs that want to draw without the CLI.
+//!
+//! Every endpoint takes the same request body: the participants, the exclusions between them and some options.
+//! - `POST /match` draws and returns the assignments, or the validation report if no draw is possible
+//! - `POST /validate` returns the same report as the `validate` subcommand
+//! - `POST /explain` returns the same report as the `analyze` subcommand: forced pairs and participants with few options
+//!
+//! Nothing is stored, so the caller is responsible for keeping the results secret.
+
+use std::io::{Cursor, Read};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{
+    analysis,
+    commitment::{Recipe, Reveal},
+    draw_statistics::DrawStatistics,
+    form_submission::{
+        configuration_from_submissions, seed_derivation_from_submissions, FormSubmission,
+    },
+    matching::{self, MatchingMethod, PermutationBudget},
+    seed, validation,
+};
+
+/// Configurations are small, anything larger than this is rejected
+const MAX_BODY_LENGTH: u64 = 1024 * 1024;
+/// Requests are handled one at a time, so larger rosters are rejected before the quadratic validation and drawing
+const MAX_PARTICIPANTS: usize = 100;
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+#[derive(Debug, Deserialize)]
+pub struct ApiParticipant {
+    pub name: String,
+    #[serde(default)]
+    pub discord_handle: String,
+    #[serde(default)]
+    pub mailing_info: String,
+    #[serde(default)]
+    pub interests: String,
+    /// Contributes to the seed like the "Your Random String" column, unless a seed is given
+    #[serde(default)]
+    pub random_string: String,
+}
+
+/// `sender` must not be the Secret Santa for `recipient`
+#[derive(Debug, Deserialize)]
+pub struct ApiExclusion {
+    pub sender: String,
+    pub recipient: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiRequest {
+    pub participants: Vec<ApiParticipant>,
+    #[serde(default)]
+    pub exclusions: Vec<ApiExclusion>,
+    /// Hex-encoded seed to reproduce a draw
+    pub seed: Option<String>,
+    #[serde(default = "default_method")]
+    pub method: MatchingMethod,
+    /// Threshold for `/validate`, see `validate --few-eligible-threshold`
+    #[serde(default = "default_threshold")]
+    pub few_eligible_threshold: usize,
+    /// Threshold for `/explain`, see `analyze --low-entropy-threshold`
+    #[serde(default = "default_threshold")]
+    pub low_entropy_threshold: usize,
+}
+
+fn default_method() -> MatchingMethod {
+    MatchingMethod::FlowNetwork
+}
+
+fn default_threshold() -> usize {
+    1
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiAssignment {
+    pub sender: String,
+    pub recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchResponse {
+    /// Hex-encoded seed that reproduces this draw
+    pub seed: String,
+    pub commitment: String,
+    pub assignments: Vec<ApiAssignment>,
+    pub statistics: DrawStatistics,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse<T: Serialize> {
+    error: String,
+    diagnostics: Option<T>,
+}
+
+impl ApiRequest {
+    /// The request in the shape of sign-up responses, so that it goes through the same code as a `.csv` file.
+    pub fn to_submissions(&self) -> Vec<FormSubmission> {
+        self.participants
+            .iter()
+            .map(|participant| FormSubmission {
+                timestamp: String::new(),
+                name: participant.name.clone(),
+                discord_handle: participant.discord_handle.clone(),
+                cannot_send_to_submitter: self
+                    .exclusions
+                    .iter()
+                    .filter(|exclusion| exclusion.recipient == participant.name)
+                    .map(|exclusion| exclusion.sender.clone())
+                    .collect(),
+                cannot_receive_from_submitter: Vec::new(),
+                mailing_info: participant.mailing_info.clone(),
+                interests: participant.interests.clone(),
+                anything_else: String::new(),
+                random_string: participant.random_string.clone(),
+            })
+            .collect()
+    }
+}
+
+fn json_response<T: Serialize>(status: u16, body: &T) -> JsonResponse {
+    Response::from_string(serde_json::to_string_pretty(body).unwrap())
+        .with_status_code(StatusCode(status))
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn error_response(status: u16, error: impl Into<String>) -> JsonResponse {
+    json_response::<ErrorResponse<()>>(
+        status,
+        &ErrorResponse {
+            error: error.into(),
+            diagnostics: None,
+        },
+    )
+}
+
+/// Draws an assignment for the request, or returns the validation report explaining why it can't.
+pub fn draw(
+    request: &ApiRequest,
+) -> Result<MatchResponse, (String, Option<Box<validation::ValidationReport>>)> {
+    let submissions = request.to_submissions();
+    let report = validation::validate(&submissions, request.few_eligible_threshold);
+    if report.has_errors() {
+        return Err((
+            "The configuration is not valid".to_string(),
+            Some(Box::new(report)),
+        ));
+    }
+
+    let seed = match &request.seed {
+        Some(seed) => seed::parse_seed(seed).map_err(|message| (message, None))?,
+        None => {
+            let seed_derivation = seed_derivation_from_submissions(&submissions);
+            if seed_derivation.has_contributions() {
+                seed_derivation.seed
+            } else {
+                seed::random_seed()
+            }
+        }
+    };
+    let configuration = configuration_from_submissions(&submissions);
+    let permutation = matching::generate_permutation(
+        &request.method,
+        &configuration,
+        false,
+        &PermutationBudget::default(),
+        &mut seed::rng_from_seed(seed),
+    )
+    .map_err(|message| (message, None))?;
+
+    let reveal = Reveal::new(
+        &permutation,
+        seed,
+        Recipe::Draw {
+            method: request
+                .method
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+        },
+    );
+    Ok(MatchResponse {
+        seed: hex::encode(seed),
+        commitment: reveal.commitment(),
+        assignments: reveal
+            .assignments
+            .iter()
+            .map(|assignment| ApiAssignment {
+                sender: assignment.sender.clone(),
+                recipient: assignment.recipient.clone(),
+            })
+            .collect(),
+        statistics: DrawStatistics::new(&permutation, &configuration),
+    })
+}
+
+fn handle_request(request: &mut Request) -> JsonResponse {
+    if *request.method() != Method::Post {
+        return error_response(405, "Only POST is supported");
+    }
+    let endpoint = request.url().to_string();
+    if !["/match", "/validate", "/explain"].contains(&endpoint.as_str()) {
+        return error_response(404, format!("Unknown endpoint {}", endpoint));
+    }
+
+    let mut body = String::new();
+    if let Err(error) = request
+        .as_reader()
+        .take(MAX_BODY_LENGTH + 1)
+        .read_to_string(&mut body)
+    {
+        return error_response(400, format!("Failed to read the request: {}", error));
+    }
+    if body.len() as u64 > MAX_BODY_LENGTH {
+        return error_response(413, "The request is too large");
+    }
+    respond(&endpoint, &body)
+}
+
+/// Answers a request to one of the endpoints, given its body.
+fn respond(endpoint: &str, body: &str) -> JsonResponse {
+    let api_request: ApiRequest = match serde_json::from_str(body) {
+        Ok(api_request) => api_request,
+        Err(error) => return error_response(400, format!("Invalid request: {}", error)),
+    };
+    if api_request.participants.len() > MAX_PARTICIPANTS {
+        return error_response(
+            400,
+            format!(
+                "At most {} participants are supported, got {}",
+                MAX_PARTICIPANTS,
+                api_request.participants.len()
+            ),
+        );
+    }
+
+    match endpoint {
+        "/match" => match draw(&api_request) {
+            Ok(response) => json_response(200, &response),
+            Err((error, diagnostics)) => json_response(422, &ErrorResponse { error, diagnostics }),
+        },
+        "/validate" => json_response(
+            200,
+            &validation::validate(
+                &api_request.to_submissions(),
+                api_request.few_eligible_threshold,
+            ),
+        ),
+        _ => json_response(
+            200,
+            &analysis::analyze(
+                &configuration_from_submissions(&api_request.to_submissions()),
+                api_request.low_entropy_threshold,
+            ),
+        ),
+    }
+}
+
+/// Serves the API until the process is stopped.
+pub fn serve(address: &str) -> Result<(), String> {
+    let server = Server::http(address)
+        .map_err(|error| format!("Failed to listen on {}: {}", address, error))?;
+    eprintln!("Serving the matching API on http://{}/", address);
+
+    for mut request in server.incoming_requests() {
+        let response = handle_request(&mut request);
+        if let Err(error) = request.respond(response) {
+            eprintln!("Failed to respond: {}", error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_request(exclusions: &str) -> ApiRequest {
+        serde_json::from_str(&format!(
+            r#"{{
+                "participants": [{{"name": "Alice"}}, {{"name": "Bob"}}, {{"name": "Charlie"}}],
+                "exclusions": {},
+                "seed": "{}"
+            }}"#,
+            exclusions,
+            "00".repeat(32)
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_match() {
+        let request = get_test_request(r#"[{"sender": "Alice", "recipient": "Bob"}]"#);
+
+        let response = draw(&request).unwrap();
+
+        // Alice can't send to Bob, so the only valid cycle is Alice -> Charlie -> Bob -> Alice
+        assert_eq!(response.assignments[0].sender, "Alice");
+        assert_eq!(response.assignments[0].recipient, "Charlie");
+        assert_eq!(response.statistics.cycle_count, 1);
+    }
+
+    #[test]
+    fn test_match_reports_infeasibility() {
+        let request = get_test_request(
+            r#"[{"sender": "Alice", "recipient": "Bob"}, {"sender": "Alice", "recipient": "Charlie"}]"#,
+        );
+
+        let (_, diagnostics) = draw(&request).unwrap_err();
+
+        assert!(!diagnostics.unwrap().feasible);
+    }
+
+    #[test]
+    fn test_rejects_large_rosters() {
+        let participants: Vec<String> = (0..=MAX_PARTICIPANTS)
+            .map(|index| format!(r#"{{"name": "Participant {}"}}"#, index))
+            .collect();
+        let body = format!(r#"{{"participants": [{}]}}"#, participants.join(", "));
+
+        let response = respond("/match", &body);
+
+        assert_eq!(response.status_code().0, 400);
+        let mut body = String::new();
+        response.into_reader().read_to_string(&mut body).unwrap();
+        assert!(body.contains("At most 100 participants are supported, got 101"));
+    }
+}
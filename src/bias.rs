@@ -0,0 +1,225 @@
+//! Measures how far a matching method is from drawing every valid assignment equally often.
+//!
+//! Under a uniform draw, the probability that a sender gets a given recipient is the fraction of valid assignments
+//! containing that pair, which is the permanent of the allowed-pair matrix without that sender's row and recipient's column
+//! divided by the permanent of the whole matrix. Running a method many times and comparing how often each pair comes up
+//! with these probabilities shows how biased the method is.
+
+use std::{fmt::Write, rc::Rc};
+
+use serde::Serialize;
+
+use crate::{
+    configuration::{Configuration, Participant},
+    counting,
+    permutation::Permutation,
+};
+
+/// Groups up to this size get exact uniform expectations
+pub const EXACT_EXPECTATION_LIMIT: usize = 16;
+
+#[derive(Debug, Serialize)]
+pub struct PairFrequency {
+    pub sender: String,
+    pub recipient: String,
+    /// The probability of this pair under a uniform draw
+    pub expected: f64,
+    /// The fraction of runs that contained this pair
+    pub observed: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodBias {
+    pub method: String,
+    pub runs: usize,
+    /// Runs that didn't produce a valid assignment, which aren't counted anywhere else
+    pub failures: usize,
+    /// Pearson's chi-square statistic, summed over every sender's distribution of recipients.
+    /// Its expected value for an unbiased method is the degrees of freedom, but since the senders' recipients depend on each other
+    /// it varies more than a true chi-square distribution would, so only large multiples of it point to bias.
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    /// The largest absolute difference between an observed and expected pair frequency
+    pub max_deviation: f64,
+    pub pairs: Vec<PairFrequency>,
+}
+
+impl MethodBias {
+    pub fn to_human(&self, shown_pairs: usize) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{}: {} runs, {} failed, chi-square {:.1} with {} degrees of freedom, max deviation {:.4}",
+            self.method,
+            self.runs,
+            self.failures,
+            self.chi_square,
+            self.degrees_of_freedom,
+            self.max_deviation
+        )
+        .unwrap();
+
+        let mut pairs: Vec<&PairFrequency> = self.pairs.iter().collect();
+        pairs.sort_by(|a, b| {
+            (b.observed - b.expected)
+                .abs()
+                .total_cmp(&(a.observed - a.expected).abs())
+        });
+        for pair in pairs.into_iter().take(shown_pairs) {
+            writeln!(
+                report,
+                "  {} -> {}: expected {:.4}, observed {:.4}",
+                pair.sender, pair.recipient, pair.expected, pair.observed
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+/// The probability of every (sender, recipient) pair under a uniform draw, with participants sorted by name.
+pub fn uniform_pair_probabilities(configuration: &Configuration) -> Result<Vec<Vec<f64>>, String> {
+    let matrix = counting::allowed_matrix(configuration);
+    let n = matrix.len();
+    if n > EXACT_EXPECTATION_LIMIT {
+        return Err(format!(
+            "Measuring bias needs the exact uniform expectation, which is only computed for up to {} participants",
+            EXACT_EXPECTATION_LIMIT
+        ));
+    }
+
+    let total = counting::permanent(&matrix);
+    if total == 0 {
+        return Err("There is no valid assignment, so there is nothing to measure".to_string());
+    }
+
+    Ok((0..n)
+        .map(|sender| {
+            (0..n)
+                .map(|recipient| {
+                    if !matrix[sender][recipient] {
+                        return 0.0;
+                    }
+                    let minor: Vec<Vec<bool>> = (0..n)
+                        .filter(|&row| row != sender)
+                        .map(|row| {
+                            (0..n)
+                                .filter(|&column| column != recipient)
+                                .map(|column| matrix[row][column])
+                                .collect()
+                        })
+                        .collect();
+                    counting::permanent(&minor) as f64 / total as f64
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Runs `sample` `runs` times and compares the pair frequencies with the `expected` probabilities.
+pub fn measure_bias<F>(
+    method: String,
+    configuration: &Configuration,
+    expected: &[Vec<f64>],
+    runs: usize,
+    mut sample: F,
+) -> MethodBias
+where
+    F: FnMut() -> Result<Permutation<Rc<Participant>>, String>,
+{
+    let participants = configuration.sorted_participants();
+    let index_of =
+        |participant: &Rc<Participant>| participants.iter().position(|p| p == participant).unwrap();
+
+    let n = participants.len();
+    let mut counts = vec![vec![0usize; n]; n];
+    let mut failures = 0;
+    for _ in 0..runs {
+        match sample() {
+            Ok(permutation) => {
+                for assignment in permutation.assignments.iter() {
+                    counts[index_of(&assignment.sender)][index_of(&assignment.recipient)] += 1;
+                }
+            }
+            Err(_) => failures += 1,
+        }
+    }
+
+    let successes = runs - failures;
+    let mut chi_square = 0.0;
+    let mut degrees_of_freedom = 0;
+    let mut max_deviation: f64 = 0.0;
+    let mut pairs = Vec::new();
+    for sender in 0..n {
+        let possible_recipients = expected[sender].iter().filter(|&&p| p > 0.0).count();
+        degrees_of_freedom += possible_recipients.saturating_sub(1);
+        for recipient in 0..n {
+            if !configuration.allows(&participants[sender], &participants[recipient]) {
+                continue;
+            }
+            let expected_count = expected[sender][recipient] * successes as f64;
+            let observed_count = counts[sender][recipient] as f64;
+            if expected_count > 0.0 {
+                chi_square += (observed_count - expected_count).powi(2) / expected_count;
+            }
+            let observed = if successes == 0 {
+                0.0
+            } else {
+                observed_count / successes as f64
+            };
+            max_deviation = max_deviation.max((observed - expected[sender][recipient]).abs());
+            pairs.push(PairFrequency {
+                sender: participants[sender].name.clone(),
+                recipient: participants[recipient].name.clone(),
+                expected: expected[sender][recipient],
+                observed,
+            });
+        }
+    }
+
+    MethodBias {
+        method,
+        runs,
+        failures,
+        chi_square,
+        degrees_of_freedom,
+        max_deviation,
+        pairs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::test_support;
+
+    #[test]
+    fn test_uniform_pair_probabilities() {
+        let configuration = test_support::configuration(&["Alice", "Bob", "Charlie", "David"], &[]);
+
+        let expected = uniform_pair_probabilities(&configuration).unwrap();
+
+        // Each of the 3 possible recipients is equally likely
+        for (sender, row) in expected.iter().enumerate() {
+            for (recipient, &probability) in row.iter().enumerate() {
+                let uniform = if sender == recipient { 0.0 } else { 1.0 / 3.0 };
+                assert!((probability - uniform).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_always_the_same_assignment_is_biased() {
+        let configuration = test_support::configuration(&["Alice", "Bob", "Charlie", "David"], &[]);
+        let expected = uniform_pair_probabilities(&configuration).unwrap();
+        let cycle = || Ok(test_support::cycle(&configuration));
+
+        let bias = measure_bias("cycle".to_string(), &configuration, &expected, 100, cycle);
+
+        assert_eq!(bias.failures, 0);
+        assert_eq!(bias.degrees_of_freedom, 8);
+        assert!((bias.max_deviation - 2.0 / 3.0).abs() < 1e-9);
+        // Each sender contributes (100 - 33.3)^2 / 33.3 + 2 * 33.3 = 200
+        assert!((bias.chi_square - 800.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,392 @@
+//! A commit-reveal scheme so that participants can check the organizer did not rig the draw.
+//!
+//! Before any files are delivered, the organizer publishes a commitment (a Merkle root) to the full permutation and the seed that produced it.
+//! Every assignment is a leaf of the tree, salted so that the pairing can't be brute-forced from the commitment,
+//! and every participant's file contains the path from their leaf to the root. The seed is the final leaf.
+//! The seed leaf also commits to the recipe of the draw (e.g. the matching method), so that the draw can be rerun.
+//! After the event, the organizer publishes the reveal (the seed, the recipe and every assignment with its salt)
+//! and anyone can recompute the commitment, check the revealed mapping against the exclusion constraints
+//! and rerun the draw from the seed to check that it gives exactly the revealed mapping.
+
+use std::{fmt::Display, rc::Rc, str::FromStr};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    configuration::{Configuration, Participant},
+    permutation::{Assignment, Permutation},
+    seed::Seed,
+};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const SEED_PREFIX: u8 = 0x02;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RevealedAssignment {
+    pub sender: String,
+    pub recipient: String,
+    /// Hex-encoded random salt that hides this assignment inside the commitment
+    pub salt: String,
+}
+
+/// How a draw was made from its seed, so that anyone can make it again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum Recipe {
+    /// A draw with the given matching method, e.g. "flow-network"
+    Draw { method: String },
+    /// A repair of the draw with the commitment `parent` after the `removed` participants (sorted) dropped out
+    Repair {
+        parent: String,
+        removed: Vec<String>,
+    },
+    /// The draw with the commitment `parent` with the `added` late joiners spliced in, in this order
+    Insert { parent: String, added: Vec<String> },
+    /// Round `round` (counting from 1) of `rounds` rounds drawn together with these constraints
+    Round {
+        round: usize,
+        rounds: usize,
+        min_cycle_length: usize,
+        no_mutual: bool,
+    },
+}
+
+/// Everything needed to recompute a commitment. Keep this private until the exchange is over.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Reveal {
+    /// Hex-encoded seed that drove the random draw
+    pub seed: String,
+    /// How the draw was made, so that it can be rerun
+    pub recipe: Recipe,
+    /// Sorted by sender name so that the tree (and therefore the commitment) is reproducible
+    pub assignments: Vec<RevealedAssignment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofStep {
+    pub sibling: [u8; 32],
+    pub sibling_is_left: bool,
+}
+
+/// The path from a single assignment's leaf up to the committed root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub salt: String,
+    pub path: Vec<ProofStep>,
+}
+
+impl Display for InclusionProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.salt)?;
+        for step in self.path.iter() {
+            let side = if step.sibling_is_left { 'L' } else { 'R' };
+            write!(f, ":{}{}", side, hex::encode(step.sibling))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InclusionProof {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let salt = parts
+            .next()
+            .filter(|salt| !salt.is_empty())
+            .ok_or_else(|| "Proof is missing its salt".to_string())?
+            .to_string();
+        let path = parts
+            .map(|part| {
+                let sibling_is_left = match part.chars().next() {
+                    Some('L') => true,
+                    Some('R') => false,
+                    _ => return Err(format!("Malformed proof step {:?}", part)),
+                };
+                Ok(ProofStep {
+                    sibling: decode_hash(&part[1..])?,
+                    sibling_is_left,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(InclusionProof { salt, path })
+    }
+}
+
+fn decode_hash(hex_string: &str) -> Result<[u8; 32], String> {
+    hex::decode(hex_string)
+        .map_err(|error| format!("Invalid hex {:?}: {}", hex_string, error))?
+        .try_into()
+        .map_err(|_| format!("Expected a 32 byte hash, got {:?}", hex_string))
+}
+
+fn hash_leaf(assignment: &RevealedAssignment) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    // Length-prefix every field so that no two different assignments can produce the same byte string
+    for field in [&assignment.salt, &assignment.sender, &assignment.recipient] {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn hash_seed(seed: &str, recipe: &Recipe) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([SEED_PREFIX]);
+    hasher.update(seed.as_bytes());
+    let recipe = serde_json::to_string(recipe).unwrap();
+    hasher.update((recipe.len() as u64).to_be_bytes());
+    hasher.update(recipe.as_bytes());
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Returns every level of the Merkle tree, starting with the leaves and ending with the root.
+/// A node without a sibling is carried up to the next level unchanged.
+fn merkle_levels(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let next_level = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [only] => *only,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next_level);
+    }
+    levels
+}
+
+impl Reveal {
+    pub fn new(permutation: &Permutation<Rc<Participant>>, seed: Seed, recipe: Recipe) -> Reveal {
+        let mut rng = rand::thread_rng();
+        let mut assignments: Vec<RevealedAssignment> = permutation
+            .assignments
+            .iter()
+            .map(|assignment| {
+                let mut salt = [0u8; 16];
+                rng.fill_bytes(&mut salt);
+                RevealedAssignment {
+                    sender: assignment.sender.name.clone(),
+                    recipient: assignment.recipient.name.clone(),
+                    salt: hex::encode(salt),
+                }
+            })
+            .collect();
+        assignments.sort_by(|a, b| a.sender.cmp(&b.sender));
+        Reveal {
+            seed: hex::encode(seed),
+            recipe,
+            assignments,
+        }
+    }
+
+    fn levels(&self) -> Vec<Vec<[u8; 32]>> {
+        merkle_levels(
+            self.assignments
+                .iter()
+                .map(hash_leaf)
+                .chain(std::iter::once(hash_seed(&self.seed, &self.recipe)))
+                .collect(),
+        )
+    }
+
+    /// Hex-encoded Merkle root over every assignment. This is the value to publish before delivering files.
+    pub fn commitment(&self) -> String {
+        // There is always at least the seed leaf, so the root level is never empty
+        hex::encode(self.levels().last().unwrap()[0])
+    }
+
+    pub fn proof_for(&self, sender_name: &str) -> Option<InclusionProof> {
+        let leaf_index = self
+            .assignments
+            .iter()
+            .position(|assignment| assignment.sender == sender_name)?;
+        let mut index = leaf_index;
+        let levels = self.levels();
+        let mut path = Vec::new();
+        for level in levels.iter().take(levels.len() - 1) {
+            let sibling_index = index ^ 1;
+            if let Some(sibling) = level.get(sibling_index) {
+                path.push(ProofStep {
+                    sibling: *sibling,
+                    sibling_is_left: sibling_index < index,
+                });
+            }
+            index /= 2;
+        }
+        Some(InclusionProof {
+            salt: self.assignments[leaf_index].salt.clone(),
+            path,
+        })
+    }
+
+    /// Resolves the revealed names against the configuration and checks that the result is a valid draw.
+    pub fn to_permutation(
+        &self,
+        configuration: &Configuration,
+    ) -> Result<Permutation<Rc<Participant>>, String> {
+        let find_participant = |name: &str| {
+            configuration
+                .participants
+                .iter()
+                .find(|participant| participant.name == name)
+                .cloned()
+                .ok_or_else(|| format!("{:?} is not a participant in the configuration", name))
+        };
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|assignment| {
+                Ok(Assignment {
+                    sender: find_participant(&assignment.sender)?,
+                    recipient: find_participant(&assignment.recipient)?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        let permutation = Permutation::try_new(assignments, &configuration.participants)?;
+        configuration.ensure_valid_permutation(&permutation)?;
+        Ok(permutation)
+    }
+}
+
+/// Checks that a single assignment is part of the draw with the given commitment, without needing the rest of the reveal.
+pub fn verify_inclusion(
+    commitment: &str,
+    sender: &str,
+    recipient: &str,
+    proof: &InclusionProof,
+) -> Result<(), String> {
+    let expected_root = decode_hash(commitment.trim())?;
+    let root = proof.path.iter().fold(
+        hash_leaf(&RevealedAssignment {
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            salt: proof.salt.clone(),
+        }),
+        |node, step| {
+            if step.sibling_is_left {
+                hash_node(&step.sibling, &node)
+            } else {
+                hash_node(&node, &step.sibling)
+            }
+        },
+    );
+    if root != expected_root {
+        return Err(format!(
+            "The assignment {} -> {} is not part of the draw with commitment {}",
+            sender, recipient, commitment
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a revealed draw against the commitment published before delivery and against every exclusion constraint.
+pub fn verify_reveal(
+    commitment: &str,
+    reveal: &Reveal,
+    configuration: &Configuration,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    let recomputed = reveal.commitment();
+    if recomputed != commitment.trim() {
+        return Err(format!(
+            "The reveal does not match the commitment: expected {}, recomputed {}",
+            commitment.trim(),
+            recomputed
+        ));
+    }
+    reveal.to_permutation(configuration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_reveal() -> Reveal {
+        Reveal {
+            seed: hex::encode([7u8; 32]),
+            recipe: Recipe::Draw {
+                method: "flow-network".to_string(),
+            },
+            assignments: ["Alice", "Bob", "Charlie", "David", "Eve"]
+                .iter()
+                .zip(["Bob", "Charlie", "David", "Eve", "Alice"])
+                .enumerate()
+                .map(|(i, (sender, recipient))| RevealedAssignment {
+                    sender: sender.to_string(),
+                    recipient: recipient.to_string(),
+                    salt: format!("{:02x}", i),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_every_proof_verifies_against_commitment() {
+        let reveal = get_test_reveal();
+        let commitment = reveal.commitment();
+
+        for assignment in reveal.assignments.iter() {
+            let proof = reveal.proof_for(&assignment.sender).unwrap();
+            let round_tripped: InclusionProof = proof.to_string().parse().unwrap();
+            assert_eq!(proof, round_tripped);
+            assert!(verify_inclusion(
+                &commitment,
+                &assignment.sender,
+                &assignment.recipient,
+                &round_tripped
+            )
+            .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_proof_rejects_different_recipient() {
+        let reveal = get_test_reveal();
+        let commitment = reveal.commitment();
+        let proof = reveal.proof_for("Alice").unwrap();
+
+        assert!(verify_inclusion(&commitment, "Alice", "Charlie", &proof).is_err());
+    }
+
+    #[test]
+    fn test_tampered_reveal_changes_commitment() {
+        let reveal = get_test_reveal();
+        let mut tampered = reveal.clone();
+        tampered.assignments[0].recipient = "Charlie".to_string();
+        tampered.assignments[1].recipient = "Bob".to_string();
+
+        assert_ne!(reveal.commitment(), tampered.commitment());
+    }
+
+    #[test]
+    fn test_seed_is_part_of_commitment() {
+        let reveal = get_test_reveal();
+        let mut tampered = reveal.clone();
+        tampered.seed = hex::encode([8u8; 32]);
+
+        assert_ne!(reveal.commitment(), tampered.commitment());
+
+        let mut tampered = reveal.clone();
+        tampered.recipe = Recipe::Draw {
+            method: "uniform".to_string(),
+        };
+
+        assert_ne!(reveal.commitment(), tampered.commitment());
+    }
+}
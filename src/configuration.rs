@@ -19,6 +19,29 @@ impl Display for Participant {
     }
 }
 
+impl Participant {
+    /// The name as a file name: anything that isn't allowed in file names or could point to another directory
+    /// is replaced, so that a sign-up can't make the tool write outside the output directory.
+    pub fn file_name(&self) -> String {
+        let file_name: String = self
+            .name
+            .chars()
+            .map(|character| {
+                if character.is_control() || "/\\:*?\"<>|".contains(character) {
+                    '_'
+                } else {
+                    character
+                }
+            })
+            .collect();
+        if file_name.is_empty() || file_name.starts_with('.') {
+            format!("_{}", file_name)
+        } else {
+            file_name
+        }
+    }
+}
+
 impl PartialEq for Participant {
     fn eq(&self, other: &Participant) -> bool {
         self.name == other.name
@@ -41,6 +64,66 @@ pub struct Configuration {
 }
 
 impl Configuration {
+    /// Participants in a fixed order (by name), so that a seeded draw can be reproduced exactly.
+    pub fn sorted_participants(&self) -> Vec<Rc<Participant>> {
+        let mut participants: Vec<Rc<Participant>> = self.participants.iter().cloned().collect();
+        participants.sort_by(|a, b| a.name.cmp(&b.name));
+        participants
+    }
+
+    /// The same configuration with the named participants (and every exclusion mentioning them) removed.
+    pub fn without_participants(&self, names: &HashSet<String>) -> Configuration {
+        let is_kept = |participant: &Rc<Participant>| !names.contains(&participant.name);
+        let filter_exclusions =
+            |exclusions: &HashMap<Rc<Participant>, HashSet<Rc<Participant>>>| {
+                exclusions
+                    .iter()
+                    .filter(|(participant, _)| is_kept(participant))
+                    .map(|(participant, excluded)| {
+                        (
+                            participant.clone(),
+                            excluded.iter().filter(|p| is_kept(p)).cloned().collect(),
+                        )
+                    })
+                    .collect()
+            };
+
+        Configuration {
+            participants: self
+                .participants
+                .iter()
+                .filter(|p| is_kept(p))
+                .cloned()
+                .collect(),
+            cannot_send_to: filter_exclusions(&self.cannot_send_to),
+            cannot_receive_from: filter_exclusions(&self.cannot_receive_from),
+        }
+    }
+
+    /// The same configuration where none of the given (sender, recipient) pairs are allowed anymore.
+    pub fn without_pairs(&self, pairs: &[(Rc<Participant>, Rc<Participant>)]) -> Configuration {
+        let mut cannot_receive_from = self.cannot_receive_from.clone();
+        for (sender, recipient) in pairs {
+            cannot_receive_from
+                .entry(sender.clone())
+                .or_default()
+                .insert(recipient.clone());
+        }
+
+        Configuration {
+            participants: self.participants.clone(),
+            cannot_send_to: self.cannot_send_to.clone(),
+            cannot_receive_from,
+        }
+    }
+
+    /// Whether the exclusion constraints allow `sender` to be the Secret Santa for `recipient`.
+    pub fn allows(&self, sender: &Rc<Participant>, recipient: &Rc<Participant>) -> bool {
+        sender != recipient
+            && !self.cannot_send_to[recipient].contains(sender)
+            && !self.cannot_receive_from[sender].contains(recipient)
+    }
+
     pub fn ensure_exclusions_satisfied(
         &self,
         permutation: &Permutation<Rc<Participant>>,
@@ -76,3 +159,86 @@ impl Configuration {
         Ok(())
     }
 }
+
+/// Fixtures for the tests of every module that works on configurations.
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+    use crate::permutation::Assignment;
+
+    pub fn participant(name: &str) -> Rc<Participant> {
+        Rc::new(Participant {
+            name: name.to_string(),
+            discord_handle: format!("{}#0000", name.to_lowercase()),
+            mailing_info: format!("1 {} Lane", name),
+            interests: "Programming".to_string(),
+        })
+    }
+
+    /// The named participants, where the sender of each (sender, recipient) pair in `exclusions` can't send to the recipient.
+    pub fn configuration(names: &[&str], exclusions: &[(&str, &str)]) -> Configuration {
+        let participants: Vec<Rc<Participant>> =
+            names.iter().map(|name| participant(name)).collect();
+        let find = |name: &str| {
+            participants
+                .iter()
+                .find(|participant| participant.name == name)
+                .unwrap_or_else(|| panic!("{:?} is not a test participant", name))
+                .clone()
+        };
+        let no_exclusions: HashMap<Rc<Participant>, HashSet<Rc<Participant>>> = participants
+            .iter()
+            .map(|participant| (participant.clone(), HashSet::new()))
+            .collect();
+        let mut cannot_send_to = no_exclusions.clone();
+        for (sender, recipient) in exclusions {
+            cannot_send_to
+                .get_mut(&find(recipient))
+                .unwrap()
+                .insert(find(sender));
+        }
+
+        Configuration {
+            participants: participants.iter().cloned().collect(),
+            cannot_send_to,
+            cannot_receive_from: no_exclusions,
+        }
+    }
+
+    /// `size` participants called "Participant 0", "Participant 1", ... without any exclusions.
+    pub fn numbered_configuration(size: usize) -> Configuration {
+        let names: Vec<String> = (0..size)
+            .map(|index| format!("Participant {}", index))
+            .collect();
+        configuration(&names.iter().map(String::as_str).collect::<Vec<_>>(), &[])
+    }
+
+    /// Everyone sends to the next participant by name, and the last one to the first.
+    pub fn cycle(configuration: &Configuration) -> Permutation<Rc<Participant>> {
+        let participants = configuration.sorted_participants();
+        let assignments = participants
+            .iter()
+            .zip(participants.iter().cycle().skip(1))
+            .map(|(sender, recipient)| Assignment {
+                sender: sender.clone(),
+                recipient: recipient.clone(),
+            })
+            .collect();
+        Permutation::try_new(assignments, &configuration.participants).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::participant;
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(participant("Alice Smith").file_name(), "Alice Smith");
+        assert_eq!(participant("../../etc/x").file_name(), "_.._.._etc_x");
+        assert_eq!(participant("a\\b").file_name(), "a_b");
+        assert_eq!(participant("a\0b\nc").file_name(), "a_b_c");
+        assert_eq!(participant("..").file_name(), "_..");
+        assert_eq!(participant("").file_name(), "_");
+    }
+}
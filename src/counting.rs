@@ -0,0 +1,258 @@
+//! Counts the valid assignments of a configuration, i.e. how much randomness a draw actually has.
+//!
+//! The number of valid assignments is the permanent of the 0/1 matrix of allowed (sender, recipient) pairs.
+//! Computing a permanent is #P-hard, so it is done exactly with Ryser's formula in O(2^n * n) for small groups,
+//! and estimated with Rasmussen's unbiased estimator for larger ones.
+
+use std::fmt::Display;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+
+use crate::configuration::Configuration;
+
+/// Groups up to this size are counted exactly
+pub const EXACT_COUNT_LIMIT: usize = 20;
+/// Samples used by the estimator for larger groups, unless told otherwise
+pub const DEFAULT_SAMPLES: usize = 10000;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum AssignmentCount {
+    Exact {
+        count: u128,
+    },
+    Estimate {
+        count: f64,
+        standard_error: f64,
+        samples: usize,
+    },
+}
+
+impl AssignmentCount {
+    pub fn approximate(&self) -> f64 {
+        match self {
+            AssignmentCount::Exact { count } => *count as f64,
+            AssignmentCount::Estimate { count, .. } => *count,
+        }
+    }
+
+    /// How many bits of randomness a uniformly random valid assignment has
+    pub fn entropy_bits(&self) -> f64 {
+        let count = self.approximate();
+        if count < 1.0 {
+            0.0
+        } else {
+            count.log2()
+        }
+    }
+}
+
+impl Display for AssignmentCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssignmentCount::Exact { count } => write!(
+                f,
+                "{} valid assignments ({:.1} bits)",
+                count,
+                self.entropy_bits()
+            ),
+            AssignmentCount::Estimate {
+                count,
+                standard_error,
+                samples,
+            } => write!(
+                f,
+                "about {:.3e} valid assignments (± {:.1e}, {:.1} bits, estimated from {} samples)",
+                count,
+                standard_error,
+                self.entropy_bits(),
+                samples
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CountReport {
+    pub participant_count: usize,
+    pub assignment_count: AssignmentCount,
+    pub entropy_bits: f64,
+}
+
+impl CountReport {
+    pub fn new(configuration: &Configuration, assignment_count: AssignmentCount) -> CountReport {
+        CountReport {
+            participant_count: configuration.participants.len(),
+            assignment_count,
+            entropy_bits: assignment_count.entropy_bits(),
+        }
+    }
+
+    pub fn to_human(&self) -> String {
+        format!(
+            "{} participants\n{}\n",
+            self.participant_count, self.assignment_count
+        )
+    }
+}
+
+/// The allowed (sender, recipient) pairs as a matrix, with participants sorted by name.
+pub fn allowed_matrix(configuration: &Configuration) -> Vec<Vec<bool>> {
+    let participants = configuration.sorted_participants();
+    participants
+        .iter()
+        .map(|sender| {
+            participants
+                .iter()
+                .map(|recipient| configuration.allows(sender, recipient))
+                .collect()
+        })
+        .collect()
+}
+
+/// The exact permanent of a square 0/1 matrix, via Ryser's formula with Gray code ordering of the column subsets.
+pub fn permanent(matrix: &[Vec<bool>]) -> u128 {
+    let n = matrix.len();
+    if n == 0 {
+        return 1;
+    }
+
+    let mut row_sums = vec![0i128; n];
+    let mut total = 0i128;
+    for subset_index in 1u64..(1 << n) {
+        // Exactly one column enters or leaves the subset between consecutive Gray codes
+        let column = subset_index.trailing_zeros() as usize;
+        let gray_code = subset_index ^ (subset_index >> 1);
+        let delta = if gray_code & (1 << column) != 0 {
+            1
+        } else {
+            -1
+        };
+        for (row, sum) in row_sums.iter_mut().enumerate() {
+            if matrix[row][column] {
+                *sum += delta;
+            }
+        }
+
+        let product: i128 = row_sums.iter().product();
+        if (n - gray_code.count_ones() as usize).is_multiple_of(2) {
+            total += product;
+        } else {
+            total -= product;
+        }
+    }
+    total as u128
+}
+
+/// Rasmussen's estimator: fill the rows in order, picking a random free allowed column each time,
+/// and multiply the number of choices along the way. The average over many samples is an unbiased estimate of the permanent.
+pub fn estimate_permanent<R: Rng + ?Sized>(
+    matrix: &[Vec<bool>],
+    samples: usize,
+    rng: &mut R,
+) -> AssignmentCount {
+    let n = matrix.len();
+    let mut estimates = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let mut used = vec![false; n];
+        let mut estimate = 1.0;
+        for row in matrix {
+            let choices: Vec<usize> = (0..n)
+                .filter(|&column| row[column] && !used[column])
+                .collect();
+            match choices.choose(rng) {
+                Some(&column) => {
+                    estimate *= choices.len() as f64;
+                    used[column] = true;
+                }
+                None => {
+                    estimate = 0.0;
+                    break;
+                }
+            }
+        }
+        estimates.push(estimate);
+    }
+
+    let mean = estimates.iter().sum::<f64>() / samples as f64;
+    let variance = estimates
+        .iter()
+        .map(|estimate| (estimate - mean).powi(2))
+        .sum::<f64>()
+        / (samples.max(2) - 1) as f64;
+    AssignmentCount::Estimate {
+        count: mean,
+        standard_error: (variance / samples as f64).sqrt(),
+        samples,
+    }
+}
+
+/// Counts the valid assignments exactly if the group is small enough, otherwise estimates it from `samples` samples.
+pub fn count_assignments<R: Rng + ?Sized>(
+    configuration: &Configuration,
+    samples: usize,
+    rng: &mut R,
+) -> AssignmentCount {
+    let matrix = allowed_matrix(configuration);
+    if matrix.len() <= EXACT_COUNT_LIMIT {
+        AssignmentCount::Exact {
+            count: permanent(&matrix),
+        }
+    } else {
+        estimate_permanent(&matrix, samples, rng)
+    }
+}
+
+/// Fixtures for the tests of every module that works on allowed matrices.
+#[cfg(test)]
+pub mod test_support {
+    /// The allowed matrix of `n` participants without any exclusions: everyone but themselves.
+    pub fn derangement_matrix(n: usize) -> Vec<Vec<bool>> {
+        (0..n)
+            .map(|row| (0..n).map(|column| row != column).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_support::derangement_matrix, *};
+
+    #[test]
+    fn test_permanent_counts_derangements() {
+        let derangement_counts = [1, 0, 1, 2, 9, 44, 265, 1854, 14833];
+        for (n, &count) in derangement_counts.iter().enumerate() {
+            assert_eq!(permanent(&derangement_matrix(n)), count);
+        }
+        assert_eq!(permanent(&derangement_matrix(20)), 895014631192902121);
+    }
+
+    #[test]
+    fn test_permanent_with_exclusions() {
+        // Only the identity and swapping the first two
+        let matrix = vec![
+            vec![true, true, false],
+            vec![true, true, false],
+            vec![false, false, true],
+        ];
+        assert_eq!(permanent(&matrix), 2);
+    }
+
+    #[test]
+    fn test_estimate_is_close() {
+        let matrix = derangement_matrix(8);
+
+        let estimate = estimate_permanent(&matrix, 20000, &mut rand::thread_rng());
+
+        let AssignmentCount::Estimate {
+            count,
+            standard_error,
+            ..
+        } = estimate
+        else {
+            panic!("Expected an estimate");
+        };
+        assert!((count - 14833.0).abs() < 6.0 * standard_error.max(1.0));
+    }
+}
@@ -0,0 +1,256 @@
+//! The "permutation" matching method: draw random derangements until one satisfies the exclusion constraints.
+//!
+//! Derangements (permutations where nobody gets themselves) are drawn uniformly with the algorithm of Martínez,
+//! Panholzer and Prodinger, a Sattolo-style loop of random swaps that closes a cycle with exactly the probability
+//! that keeps every derangement equally likely. Drawing uniformly from all derangements and rejecting the ones that
+//! break an exclusion leaves every valid assignment equally likely, and skips the ~63% of random permutations that
+//! fail just because someone got themselves.
+//!
+//! Rejection can take arbitrarily long with tight exclusions, so the draw gives up after a budget of attempts or
+//! time. Before trying, Hall's condition is checked without any randomness, so a configuration without any valid
+//! assignment fails right away.
+
+use std::{collections::HashSet, rc::Rc};
+
+use rand::Rng;
+
+use crate::{
+    analysis,
+    configuration::{Configuration, Participant},
+    matching::PermutationBudget,
+    permutation::{Assignment, Permutation},
+};
+
+/// `probabilities[u]` is the probability of closing a cycle when `u` elements are still open,
+/// `(u - 1) * D(u - 2) / D(u)` where `D(u)` is the number of derangements of `u` elements.
+fn cycle_closing_probabilities(n: usize) -> Vec<f64> {
+    let mut probabilities = vec![0.0; n.max(2) + 1];
+    probabilities[2] = 1.0;
+    // D(u - 2) / D(u - 1), computed as a ratio because the counts themselves overflow quickly
+    let mut ratio = 0.0;
+    for (u, probability) in probabilities.iter_mut().enumerate().skip(3) {
+        *probability = ratio / (1.0 + ratio);
+        ratio = 1.0 / ((u - 1) as f64 * (1.0 + ratio));
+    }
+    probabilities
+}
+
+/// A uniformly random derangement of `0..n` (n >= 2), where element `i` is mapped to `derangement[i]`.
+fn random_derangement<R: Rng>(n: usize, rng: &mut R) -> Vec<usize> {
+    let probabilities = cycle_closing_probabilities(n);
+    let mut derangement: Vec<usize> = (0..n).collect();
+    let mut closed = vec![false; n];
+    let mut open = n;
+    let mut index = n;
+    while open >= 2 {
+        index -= 1;
+        if closed[index] {
+            continue;
+        }
+        let other = loop {
+            let other = rng.gen_range(0..index);
+            if !closed[other] {
+                break other;
+            }
+        };
+        derangement.swap(index, other);
+        if rng.gen::<f64>() < probabilities[open] {
+            closed[other] = true;
+            open -= 1;
+        }
+        open -= 1;
+    }
+    derangement
+}
+
+/// Draws random derangements until one satisfies the exclusion constraints, or gives up after the budget.
+pub fn generate_valid_permutation<R: Rng>(
+    configuration: &Configuration,
+    do_be_verbose: bool,
+    budget: &PermutationBudget,
+    rng: &mut R,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    // Don't go looking for something that doesn't exist
+    if let Some(violation) = analysis::hall_violation(configuration) {
+        return Err(format!(
+            "No valid assignment exists: {} can only send to {}",
+            violation.senders.join(", "),
+            if violation.recipients.is_empty() {
+                "nobody".to_string()
+            } else {
+                violation.recipients.join(", ")
+            }
+        ));
+    }
+
+    let participants = configuration.sorted_participants();
+    let start_time = std::time::Instant::now();
+
+    for attempt in 1..=budget.max_attempts {
+        if do_be_verbose {
+            eprintln!("Trying permutation #{}:", attempt)
+        };
+
+        let derangement = random_derangement(participants.len(), rng);
+        let rejected = participants
+            .iter()
+            .zip(derangement.iter())
+            .find(|(sender, &recipient)| !configuration.allows(sender, &participants[recipient]));
+        match rejected {
+            Some((sender, &recipient)) => {
+                if do_be_verbose {
+                    eprintln!(
+                        "{} can't send to {}",
+                        sender.name, participants[recipient].name
+                    )
+                }
+            }
+            None => {
+                let assignments: HashSet<Assignment<Rc<Participant>>> = participants
+                    .iter()
+                    .zip(derangement)
+                    .map(|(sender, recipient)| Assignment {
+                        sender: sender.clone(),
+                        recipient: participants[recipient].clone(),
+                    })
+                    .collect();
+                let permutation = Permutation::try_new(assignments, &configuration.participants)?;
+                configuration.ensure_valid_permutation(&permutation)?;
+                return Ok(permutation);
+            }
+        }
+
+        if start_time.elapsed() > budget.time_limit {
+            return Err(format!(
+                "Gave up after {} attempts in {:?} without finding a valid permutation. The exclusions are probably too tight for this method, try --matching-method uniform or flow-network.",
+                attempt,
+                start_time.elapsed()
+            ));
+        }
+    }
+
+    Err(format!(
+        "Gave up after {} attempts without finding a valid permutation. The exclusions are probably too tight for this method, try --matching-method uniform or flow-network.",
+        budget.max_attempts
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::*;
+    use crate::{configuration::test_support, seed};
+
+    #[test]
+    fn test_derangements_are_uniform() {
+        let mut rng = seed::rng_from_seed([1; 32]);
+        let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+        for _ in 0..9000 {
+            let derangement = random_derangement(4, &mut rng);
+            assert!(derangement
+                .iter()
+                .enumerate()
+                .all(|(index, &image)| index != image));
+            *counts.entry(derangement).or_default() += 1;
+        }
+
+        // There are 9 derangements of 4 elements, each should come up about 1000 times
+        assert_eq!(counts.len(), 9);
+        assert!(counts.values().all(|&count| (850..1150).contains(&count)));
+    }
+
+    #[test]
+    fn test_valid_permutation() {
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David", "Eve"],
+            &[("Alice", "Bob"), ("Bob", "Charlie")],
+        );
+        for seed in 0..10 {
+            let permutation = generate_valid_permutation(
+                &configuration,
+                false,
+                &PermutationBudget::default(),
+                &mut seed::rng_from_seed([seed; 32]),
+            )
+            .unwrap();
+            configuration
+                .ensure_valid_permutation(&permutation)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gives_up_after_the_budget() {
+        // Everyone can only send to the next person, so one of the 14833 derangements of 8 people is valid
+        let names = [
+            "Alice", "Bob", "Charlie", "David", "Eve", "Frank", "Grace", "Heidi",
+        ];
+        let mut exclusions = Vec::new();
+        for (index, sender) in names.iter().enumerate() {
+            for (other, recipient) in names.iter().enumerate() {
+                if other != index && other != (index + 1) % names.len() {
+                    exclusions.push((*sender, *recipient));
+                }
+            }
+        }
+        let configuration = test_support::configuration(&names, &exclusions);
+
+        let error = generate_valid_permutation(
+            &configuration,
+            false,
+            &PermutationBudget {
+                max_attempts: 10,
+                time_limit: Duration::MAX,
+            },
+            &mut seed::rng_from_seed([0; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert!(error.starts_with("Gave up after 10 attempts without"));
+
+        let error = generate_valid_permutation(
+            &configuration,
+            false,
+            &PermutationBudget {
+                max_attempts: u64::MAX,
+                time_limit: Duration::ZERO,
+            },
+            &mut seed::rng_from_seed([0; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert!(error.starts_with("Gave up after 1 attempts in"));
+    }
+
+    #[test]
+    fn test_infeasible_configuration_fails_before_trying() {
+        // Alice and Bob can only send to Charlie
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David"],
+            &[
+                ("Alice", "Bob"),
+                ("Alice", "David"),
+                ("Bob", "Alice"),
+                ("Bob", "David"),
+            ],
+        );
+
+        // Without the check, this budget would never run out
+        let error = generate_valid_permutation(
+            &configuration,
+            false,
+            &PermutationBudget {
+                max_attempts: u64::MAX,
+                time_limit: Duration::MAX,
+            },
+            &mut seed::rng_from_seed([0; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "No valid assignment exists: Alice, Bob can only send to Charlie"
+        );
+    }
+}
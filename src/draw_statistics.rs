@@ -0,0 +1,177 @@
+//! Statistics about a finished draw that don't reveal who got whom, so they are safe to print after every draw.
+
+use std::{collections::BTreeMap, fmt::Write, rc::Rc};
+
+use serde::Serialize;
+
+use crate::{
+    configuration::{Configuration, Participant},
+    permutation::Permutation,
+    validation::{eligibility_counts, EligibilityCount},
+};
+
+#[derive(Debug, Serialize)]
+pub struct DrawStatistics {
+    pub participant_count: usize,
+    pub cycle_count: usize,
+    /// How many cycles there are of every length
+    pub cycle_lengths: BTreeMap<usize, usize>,
+    pub longest_cycle: usize,
+    /// Participants who listed anyone in their exclusions
+    pub participants_with_exclusions: usize,
+    /// Participants the exclusions leave only one eligible recipient, so the draw had no say in who they got
+    pub participants_with_one_recipient: usize,
+    pub eligibility: Vec<EligibilityCount>,
+}
+
+impl DrawStatistics {
+    pub fn new(
+        permutation: &Permutation<Rc<Participant>>,
+        configuration: &Configuration,
+    ) -> DrawStatistics {
+        let cycles = permutation.cycles();
+        let mut cycle_lengths = BTreeMap::new();
+        for cycle in cycles.iter() {
+            *cycle_lengths.entry(cycle.len()).or_default() += 1;
+        }
+
+        let participants_with_exclusions = configuration
+            .participants
+            .iter()
+            .filter(|participant| {
+                !configuration.cannot_send_to[*participant].is_empty()
+                    || !configuration.cannot_receive_from[*participant].is_empty()
+            })
+            .count();
+        let eligibility = eligibility_counts(configuration);
+
+        DrawStatistics {
+            participant_count: permutation.assignments.len(),
+            cycle_count: cycles.len(),
+            longest_cycle: cycles.iter().map(Vec::len).max().unwrap_or(0),
+            cycle_lengths,
+            participants_with_exclusions,
+            participants_with_one_recipient: eligibility
+                .iter()
+                .filter(|count| count.eligible_recipients == 1)
+                .count(),
+            eligibility,
+        }
+    }
+
+    pub fn to_human(&self) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{} participants in {} cycle(s), the longest has {} people",
+            self.participant_count, self.cycle_count, self.longest_cycle
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "Cycle lengths: {}",
+            self.cycle_lengths
+                .iter()
+                .map(|(length, count)| format!("{} x {}", count, length))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "{} participant(s) listed exclusions, {} can only send to one person",
+            self.participants_with_exclusions, self.participants_with_one_recipient
+        )
+        .unwrap();
+        writeln!(report, "Eligible recipients / senders per participant:").unwrap();
+        for count in self.eligibility.iter() {
+            writeln!(
+                report,
+                "  {}: {} / {}",
+                count.name, count.eligible_recipients, count.eligible_senders
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configuration::test_support, permutation::Assignment};
+
+    #[test]
+    fn test_draw_statistics() {
+        // Alice doesn't want to be Charlie's santa
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David", "Eve"],
+            &[("Alice", "Charlie")],
+        );
+        let participants = configuration.sorted_participants();
+        // Alice -> Bob -> Alice, Charlie -> David -> Eve -> Charlie
+        let assignments = [(0, 1), (1, 0), (2, 3), (3, 4), (4, 2)]
+            .into_iter()
+            .map(|(sender, recipient)| Assignment {
+                sender: participants[sender].clone(),
+                recipient: participants[recipient].clone(),
+            })
+            .collect();
+        let permutation = Permutation::try_new(assignments, &configuration.participants).unwrap();
+
+        let statistics = DrawStatistics::new(&permutation, &configuration);
+
+        assert_eq!(statistics.cycle_count, 2);
+        assert_eq!(statistics.cycle_lengths, BTreeMap::from([(2, 1), (3, 1)]));
+        assert_eq!(statistics.longest_cycle, 3);
+        assert_eq!(statistics.participants_with_exclusions, 1);
+        assert_eq!(statistics.participants_with_one_recipient, 0);
+        assert_eq!(statistics.eligibility[0].eligible_recipients, 3);
+        assert!(!statistics.to_human().contains("->"));
+    }
+
+    #[test]
+    fn test_without_exclusions() {
+        let configuration = test_support::numbered_configuration(4);
+        let statistics = DrawStatistics::new(&test_support::cycle(&configuration), &configuration);
+
+        assert_eq!(statistics.participant_count, 4);
+        assert_eq!(statistics.cycle_count, 1);
+        assert_eq!(statistics.cycle_lengths, BTreeMap::from([(4, 1)]));
+        assert_eq!(statistics.participants_with_exclusions, 0);
+        assert_eq!(statistics.participants_with_one_recipient, 0);
+        assert!(statistics
+            .eligibility
+            .iter()
+            .all(|count| count.eligible_recipients == 3 && count.eligible_senders == 3));
+    }
+
+    #[test]
+    fn test_forced_recipients() {
+        // Charlie and David don't want Alice as their santa, so Alice can only send to Bob. Bob doesn't want to send to David.
+        let mut configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David"],
+            &[("Alice", "Charlie"), ("Alice", "David")],
+        );
+        let participants = configuration.sorted_participants();
+        configuration
+            .cannot_receive_from
+            .get_mut(&participants[1])
+            .unwrap()
+            .insert(participants[3].clone());
+
+        let statistics = DrawStatistics::new(&test_support::cycle(&configuration), &configuration);
+
+        assert_eq!(statistics.participants_with_exclusions, 3);
+        assert_eq!(statistics.participants_with_one_recipient, 1);
+        assert_eq!(statistics.eligibility[0].eligible_recipients, 1);
+        assert_eq!(statistics.eligibility[1].eligible_recipients, 2);
+        assert!(statistics
+            .to_human()
+            .contains("3 participant(s) listed exclusions, 1 can only send to one person"));
+
+        let json = serde_json::to_value(&statistics).unwrap();
+        assert_eq!(json["participants_with_exclusions"], 3);
+        assert_eq!(json["participants_with_one_recipient"], 1);
+    }
+}
@@ -0,0 +1,411 @@
+//! An interactive terminal editor for the exclusions of an event workspace, so that a configuration without
+//! a valid assignment can be fixed without going back to the spreadsheet.
+//!
+//! The screen shows the eligibility matrix (rows send to columns), whether a valid assignment exists and,
+//! if not, a group of senders who together have too few recipients (a Hall violation).
+//! Edits change the exclusion columns of the sign-ups stored in the workspace, so every later command sees them.
+
+use std::{collections::BTreeSet, rc::Rc};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    analysis::{self, HallViolation},
+    configuration::{Configuration, Participant},
+    form_submission::{configuration_from_submissions, FormSubmission},
+    workspace::Workspace,
+};
+
+/// Width of a row label
+const NAME_WIDTH: usize = 18;
+/// Width of a matrix cell
+const CELL_WIDTH: usize = 3;
+
+/// Allows or excludes `sender` giving a gift to `recipient` by editing both exclusion columns.
+pub fn set_excluded(
+    submissions: &mut [FormSubmission],
+    sender: &str,
+    recipient: &str,
+    excluded: bool,
+) {
+    for submission in submissions.iter_mut() {
+        if submission.name == recipient {
+            submission
+                .cannot_send_to_submitter
+                .retain(|name| name != sender);
+        }
+        if submission.name == sender {
+            submission
+                .cannot_receive_from_submitter
+                .retain(|name| name != recipient);
+            if excluded {
+                submission
+                    .cannot_receive_from_submitter
+                    .push(recipient.to_string());
+            }
+        }
+    }
+}
+
+struct Editor {
+    workspace: Workspace,
+    names: Vec<String>,
+    configuration: Configuration,
+    /// The participants of `configuration` in the same order as `names`
+    participants: Vec<Rc<Participant>>,
+    violation: Option<HallViolation>,
+    row: usize,
+    column: usize,
+    /// Participants selected for a group toggle
+    marked: BTreeSet<usize>,
+    unsaved: bool,
+    /// Whether q was pressed once with unsaved changes
+    quitting: bool,
+    message: String,
+}
+
+impl Editor {
+    fn new(workspace: Workspace) -> Editor {
+        let mut names: Vec<String> = workspace
+            .active_submissions()
+            .into_iter()
+            .map(|submission| submission.name)
+            .collect();
+        names.sort();
+        names.dedup();
+        let message = if workspace.state.draws.is_empty() {
+            String::new()
+        } else {
+            "This workspace already has draws. Edits only matter for new draws, and verifying an old draw checks it against the edited exclusions.".to_string()
+        };
+        let mut editor = Editor {
+            configuration: configuration_from_submissions(&[]),
+            participants: Vec::new(),
+            violation: None,
+            workspace,
+            names,
+            row: 0,
+            column: 0,
+            marked: BTreeSet::new(),
+            unsaved: false,
+            quitting: false,
+            message,
+        };
+        editor.refresh();
+        editor
+    }
+
+    fn refresh(&mut self) {
+        self.configuration = configuration_from_submissions(&self.workspace.active_submissions());
+        self.participants = self.configuration.sorted_participants();
+        self.violation = analysis::hall_violation(&self.configuration);
+    }
+
+    fn is_allowed(&self, sender: usize, recipient: usize) -> bool {
+        self.configuration
+            .allows(&self.participants[sender], &self.participants[recipient])
+    }
+
+    fn set_excluded(&mut self, sender: usize, recipient: usize, excluded: bool) {
+        set_excluded(
+            &mut self.workspace.state.submissions,
+            &self.names[sender],
+            &self.names[recipient],
+            excluded,
+        );
+        self.unsaved = true;
+    }
+
+    fn toggle(&mut self) {
+        if self.row == self.column {
+            return;
+        }
+        let allowed = self.is_allowed(self.row, self.column);
+        self.set_excluded(self.row, self.column, allowed);
+        self.message = format!(
+            "{} {} send to {}",
+            self.names[self.row],
+            if allowed { "can no longer" } else { "can now" },
+            self.names[self.column]
+        );
+        self.refresh();
+    }
+
+    /// Excludes every pair within the marked group, or allows them all again if they are all excluded already.
+    fn toggle_group(&mut self) {
+        if self.marked.len() < 2 {
+            self.message = "Mark at least two participants with m first".to_string();
+            return;
+        }
+        let pairs: Vec<(usize, usize)> = self
+            .marked
+            .iter()
+            .flat_map(|&sender| {
+                self.marked
+                    .iter()
+                    .map(move |&recipient| (sender, recipient))
+            })
+            .filter(|(sender, recipient)| sender != recipient)
+            .collect();
+        let exclude = pairs
+            .iter()
+            .any(|&(sender, recipient)| self.is_allowed(sender, recipient));
+        for (sender, recipient) in pairs {
+            self.set_excluded(sender, recipient, exclude);
+        }
+        self.message = format!(
+            "{} {} each other",
+            self.marked
+                .iter()
+                .map(|&index| self.names[index].as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            if exclude {
+                "can no longer send to"
+            } else {
+                "can send to"
+            }
+        );
+        self.marked.clear();
+        self.refresh();
+    }
+
+    fn save(&mut self) {
+        self.workspace.log("Edited the exclusions in the editor");
+        match self.workspace.save() {
+            Ok(()) => {
+                self.unsaved = false;
+                self.message = "Saved".to_string();
+            }
+            Err(message) => self.message = message,
+        }
+    }
+
+    /// Handles a key press, returning whether to quit.
+    fn handle_key(&mut self, key: KeyCode) -> bool {
+        let last = self.names.len().saturating_sub(1);
+        let quitting = std::mem::take(&mut self.quitting);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(last),
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(last),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle(),
+            KeyCode::Char('m') if !self.marked.remove(&self.row) => {
+                self.marked.insert(self.row);
+            }
+            KeyCode::Char('g') => self.toggle_group(),
+            KeyCode::Char('s') => self.save(),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.unsaved || quitting {
+                    return true;
+                }
+                self.quitting = true;
+                self.message = "Unsaved changes, press s to save or q again to quit without saving"
+                    .to_string();
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [status_area, matrix_area, help_area] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let status = match &self.violation {
+            None => Line::styled(
+                "A valid assignment exists",
+                Style::default().fg(Color::Green),
+            ),
+            Some(violation) => Line::styled(
+                format!(
+                    "No valid assignment: {} can only send to {}",
+                    violation.senders.join(", "),
+                    if violation.recipients.is_empty() {
+                        "nobody".to_string()
+                    } else {
+                        violation.recipients.join(", ")
+                    }
+                ),
+                Style::default().fg(Color::Red),
+            ),
+        };
+        frame.render_widget(
+            Paragraph::new(vec![status, Line::raw(self.message.as_str())])
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title(format!(
+                    " {}{} ",
+                    self.workspace.state.config.name,
+                    if self.unsaved { " (unsaved)" } else { "" }
+                ))),
+            status_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(self.matrix_lines(
+                matrix_area.height.saturating_sub(3) as usize,
+                (matrix_area.width as usize).saturating_sub(NAME_WIDTH + 2) / CELL_WIDTH,
+            ))
+            .block(Block::bordered().title(" Rows send to columns ")),
+            matrix_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(
+                "arrows/hjkl move  space toggle  m mark for group  g toggle group  s save  q quit",
+            )
+            .style(Style::default().add_modifier(Modifier::DIM)),
+            help_area,
+        );
+    }
+
+    fn matrix_lines(&self, visible_rows: usize, visible_columns: usize) -> Vec<Line<'_>> {
+        let window = |cursor: usize, visible: usize| {
+            let start = (cursor + 1).saturating_sub(visible.max(1));
+            start..(start + visible).min(self.names.len())
+        };
+        let rows = window(self.row, visible_rows);
+        let columns = window(self.column, visible_columns);
+        let violating = |names: Option<&Vec<String>>, index: usize| {
+            names.is_some_and(|names| names.contains(&self.names[index]))
+        };
+        let red = Style::default().fg(Color::Red);
+
+        let mut header = vec![Span::raw(" ".repeat(NAME_WIDTH))];
+        for column in columns.clone() {
+            let style = if violating(
+                self.violation
+                    .as_ref()
+                    .map(|violation| &violation.recipients),
+                column,
+            ) {
+                red
+            } else {
+                Style::default()
+            };
+            header.push(Span::styled(
+                format!("{:>width$}", column + 1, width = CELL_WIDTH),
+                style,
+            ));
+        }
+
+        let mut lines = vec![Line::from(header)];
+        for row in rows {
+            let mut label = format!(
+                "{}{:>3} {}",
+                if self.marked.contains(&row) { "*" } else { " " },
+                row + 1,
+                self.names[row]
+            );
+            label = format!("{:width$.width$}", label, width = NAME_WIDTH);
+            let style = if violating(
+                self.violation.as_ref().map(|violation| &violation.senders),
+                row,
+            ) {
+                red
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![Span::styled(label, style)];
+            for column in columns.clone() {
+                let symbol = if row == column {
+                    " - "
+                } else if self.is_allowed(row, column) {
+                    " · "
+                } else {
+                    " x "
+                };
+                let style = if (row, column) == (self.row, self.column) {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else if row == column {
+                    Style::default().add_modifier(Modifier::DIM)
+                } else {
+                    Style::default()
+                };
+                spans.push(Span::styled(symbol, style));
+            }
+            lines.push(Line::from(spans));
+        }
+        lines
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|error| format!("Failed to draw: {}", error))?;
+            if let Event::Key(key) =
+                event::read().map_err(|error| format!("Failed to read input: {}", error))?
+            {
+                if key.kind == KeyEventKind::Press && self.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Opens the editor on the workspace until the organizer quits.
+pub fn edit(workspace: Workspace) -> Result<(), String> {
+    if workspace.active_submissions().is_empty() {
+        return Err("This workspace doesn't have any sign-ups yet".to_string());
+    }
+    let mut editor = Editor::new(workspace);
+    let mut terminal = ratatui::init();
+    let result = editor.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(name: &str, cannot_send_to_submitter: &[&str]) -> FormSubmission {
+        FormSubmission {
+            timestamp: String::new(),
+            name: name.to_string(),
+            discord_handle: String::new(),
+            cannot_send_to_submitter: cannot_send_to_submitter
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            cannot_receive_from_submitter: Vec::new(),
+            mailing_info: String::new(),
+            interests: String::new(),
+            anything_else: String::new(),
+            random_string: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_set_excluded() {
+        // Alice isn't allowed to send to Bob
+        let mut submissions = vec![submission("Alice", &[]), submission("Bob", &["Alice"])];
+        let allows = |submissions: &[FormSubmission], sender: usize, recipient: usize| {
+            let configuration = configuration_from_submissions(submissions);
+            let participants = configuration.sorted_participants();
+            configuration.allows(&participants[sender], &participants[recipient])
+        };
+        assert!(!allows(&submissions, 0, 1));
+
+        set_excluded(&mut submissions, "Alice", "Bob", false);
+        assert!(allows(&submissions, 0, 1));
+
+        set_excluded(&mut submissions, "Bob", "Alice", true);
+        assert!(!allows(&submissions, 1, 0));
+        assert!(allows(&submissions, 0, 1));
+    }
+}
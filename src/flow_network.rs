@@ -26,8 +26,13 @@ use petgraph::{
     graph::{DiGraph, NodeIndex},
     visit::EdgeRef,
 };
+use rand::Rng;
 
-use crate::{configuration::Participant, permutation::Assignment, random_ford_fulkerson};
+use crate::{
+    configuration::{Configuration, Participant},
+    permutation::Assignment,
+    random_ford_fulkerson,
+};
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum NodeLabel {
@@ -54,11 +59,63 @@ pub struct FlowNetwork<NodeDataType, EdgeDataType> {
     sink: NodeIndex,
 }
 
+impl<EdgeDataType> FlowNetwork<NodeLabel, EdgeDataType> {
+    pub fn graph(&self) -> &DiGraph<NodeLabel, EdgeDataType> {
+        &self.graph
+    }
+
+    /// The network with only the edges that carry flow in the given assignments, each with a flow of 1.
+    pub fn flow_for(
+        &self,
+        assignments: &HashSet<Assignment<Rc<Participant>>>,
+    ) -> DiGraph<NodeLabel, usize> {
+        let mut flow = DiGraph::<NodeLabel, usize>::new();
+        for node in self.graph.node_indices() {
+            flow.add_node(self.graph[node].clone());
+        }
+        for edge in self.graph.raw_edges() {
+            let carries_flow = match (&self.graph[edge.source()], &self.graph[edge.target()]) {
+                (NodeLabel::Sender(sender), NodeLabel::Receiver(recipient)) => {
+                    assignments.iter().any(|assignment| {
+                        &assignment.sender == sender && &assignment.recipient == recipient
+                    })
+                }
+                // Everyone sends and receives exactly once
+                _ => true,
+            };
+            if carries_flow {
+                flow.add_edge(edge.source(), edge.target(), 1);
+            }
+        }
+        flow
+    }
+
+    /// Every (sender, recipient) pair the exclusions allow, i.e. the edges of the bipartite part of the network.
+    pub fn allowed_pairs(&self) -> Vec<(Rc<Participant>, Rc<Participant>)> {
+        self.graph
+            .raw_edges()
+            .iter()
+            .filter_map(
+                |edge| match (&self.graph[edge.source()], &self.graph[edge.target()]) {
+                    (NodeLabel::Sender(sender), NodeLabel::Receiver(recipient)) => {
+                        Some((sender.clone(), recipient.clone()))
+                    }
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
 pub fn construct_flow_network(
     participants: &HashSet<Rc<Participant>>,
     cannot_send_to: &HashMap<Rc<Participant>, HashSet<Rc<Participant>>>,
     cannot_receive_from: &HashMap<Rc<Participant>, HashSet<Rc<Participant>>>,
 ) -> FlowNetwork<NodeLabel, usize> {
+    // Iterate in a fixed order so that a seeded draw always sees the same graph
+    let mut participants: Vec<&Rc<Participant>> = participants.iter().collect();
+    participants.sort_by(|a, b| a.name.cmp(&b.name));
+
     // maps a person to the index of their sending and receiving node
     let mut node_owners: HashMap<Rc<Participant>, (NodeIndex, NodeIndex)> = HashMap::new();
     let mut flow_graph = DiGraph::<NodeLabel, usize>::new();
@@ -66,7 +123,7 @@ pub fn construct_flow_network(
     let source = flow_graph.add_node(NodeLabel::Source);
     let sink = flow_graph.add_node(NodeLabel::Sink);
 
-    for p in participants {
+    for &p in participants.iter() {
         let p_s = flow_graph.add_node(NodeLabel::Sender(p.clone()));
         let p_r = flow_graph.add_node(NodeLabel::Receiver(p.clone()));
         flow_graph.add_edge(source, p_s, 1);
@@ -74,8 +131,8 @@ pub fn construct_flow_network(
         node_owners.insert(p.clone(), (p_s, p_r));
     }
 
-    for sender in participants {
-        for receiver in participants {
+    for &sender in participants.iter() {
+        for &receiver in participants.iter() {
             if sender == receiver {
                 continue;
             }
@@ -96,13 +153,18 @@ pub fn construct_flow_network(
     }
 }
 
-pub fn get_matchings(
+pub fn get_matchings<R: Rng + ?Sized>(
     participants: &HashSet<Rc<Participant>>,
     flow_network: FlowNetwork<NodeLabel, usize>,
     be_verbose: bool,
+    rng: &mut R,
 ) -> Result<HashSet<Assignment<Rc<Participant>>>, HashSet<NodeLabel>> {
-    let (flow, edge_capacities) =
-        random_ford_fulkerson::ford_fulkerson(&flow_network.graph, flow_network.source, flow_network.sink);
+    let (flow, edge_capacities) = random_ford_fulkerson::ford_fulkerson(
+        &flow_network.graph,
+        flow_network.source,
+        flow_network.sink,
+        rng,
+    );
 
     // If the flow is not equal to the number of participants, then that means
     // there is at least one participant who is not receiving a gift (a matching is impossible)
@@ -147,9 +209,6 @@ pub fn get_matchings(
         eprintln!("{}", Dot::new(&post_network));
     }
 
-    // TODO: Write this representation to a file.
-    // Also, write an anonymized version of the representation (i.e. replace names with numbers) to a file.
-
     for (edge_capacity, edge) in zip(
         edge_capacities.iter(),
         flow_network.graph.raw_edges().iter(),
@@ -170,6 +229,135 @@ pub fn get_matchings(
     Ok(assignments)
 }
 
+/// Finds a random valid assignment for the configuration, or explains which participants make it impossible.
+pub fn try_generate_assignments<R: Rng>(
+    configuration: &Configuration,
+    be_verbose: bool,
+    rng: &mut R,
+) -> Result<HashSet<Assignment<Rc<Participant>>>, String> {
+    let flow_network = construct_flow_network(
+        &configuration.participants,
+        &configuration.cannot_send_to,
+        &configuration.cannot_receive_from,
+    );
+
+    get_matchings(&configuration.participants, flow_network, be_verbose, rng).map_err(
+        |problematic_nodes| {
+            format!(
+                "Failed to find a valid assignment: {}",
+                problematic_nodes
+                    .into_iter()
+                    .filter_map(|p| {
+                        match p {
+                            NodeLabel::Sender(p) => {
+                                Some(format!("{} is unable to send to anyone", p.name))
+                            }
+                            NodeLabel::Receiver(p) => {
+                                Some(format!("{} is unable to receive from anyone", p.name))
+                            }
+                            _ => None,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        },
+    )
+}
+
+/// Finds random assignments where every participant sends and receives as many gifts as their capacities say.
+/// Every (sender, recipient) edge keeps a capacity of 1, so nobody receives two gifts from the same santa.
+pub fn try_generate_multi_assignments<R: Rng>(
+    configuration: &Configuration,
+    send_capacity: impl Fn(&Participant) -> usize,
+    receive_capacity: impl Fn(&Participant) -> usize,
+    rng: &mut R,
+) -> Result<HashSet<Assignment<Rc<Participant>>>, String> {
+    let total_sent: usize = configuration
+        .participants
+        .iter()
+        .map(|participant| send_capacity(participant))
+        .sum();
+    let total_received: usize = configuration
+        .participants
+        .iter()
+        .map(|participant| receive_capacity(participant))
+        .sum();
+    if total_sent != total_received {
+        return Err(format!(
+            "The capacities don't add up: {} gifts would be sent, but {} received",
+            total_sent, total_received
+        ));
+    }
+
+    let mut flow_network = construct_flow_network(
+        &configuration.participants,
+        &configuration.cannot_send_to,
+        &configuration.cannot_receive_from,
+    );
+    let graph = &mut flow_network.graph;
+    for edge in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge).unwrap();
+        let capacity = match (&graph[source], &graph[target]) {
+            (NodeLabel::Source, NodeLabel::Sender(participant)) => send_capacity(participant),
+            (NodeLabel::Receiver(participant), NodeLabel::Sink) => receive_capacity(participant),
+            _ => continue,
+        };
+        graph[edge] = capacity;
+    }
+
+    let (flow, edge_flows) = random_ford_fulkerson::ford_fulkerson(
+        &flow_network.graph,
+        flow_network.source,
+        flow_network.sink,
+        rng,
+    );
+
+    let graph = &flow_network.graph;
+    if flow != total_sent {
+        let mut problems: Vec<String> = graph
+            .raw_edges()
+            .iter()
+            .zip(edge_flows.iter())
+            .filter(|(edge, &edge_flow)| edge_flow < edge.weight)
+            .filter_map(
+                |(edge, edge_flow)| match (&graph[edge.source()], &graph[edge.target()]) {
+                    (NodeLabel::Source, NodeLabel::Sender(participant)) => Some(format!(
+                        "{} can only send {} of {} gifts",
+                        participant.name, edge_flow, edge.weight
+                    )),
+                    (NodeLabel::Receiver(participant), NodeLabel::Sink) => Some(format!(
+                        "{} can only receive {} of {} gifts",
+                        participant.name, edge_flow, edge.weight
+                    )),
+                    _ => None,
+                },
+            )
+            .collect();
+        problems.sort();
+        return Err(format!(
+            "Failed to find a valid assignment: {}",
+            problems.join(", ")
+        ));
+    }
+
+    Ok(graph
+        .raw_edges()
+        .iter()
+        .zip(edge_flows.iter())
+        .filter(|(_, &edge_flow)| edge_flow > 0)
+        .filter_map(
+            |(edge, _)| match (&graph[edge.source()], &graph[edge.target()]) {
+                (NodeLabel::Sender(sender), NodeLabel::Receiver(recipient)) => Some(Assignment {
+                    sender: sender.clone(),
+                    recipient: recipient.clone(),
+                }),
+                _ => None,
+            },
+        )
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,7 +512,8 @@ mod tests {
 
         let flow_network =
             construct_flow_network(&participants, &cannot_send_to, &cannot_receive_from);
-        let assignments = get_matchings(&participants, flow_network, false).unwrap();
+        let assignments =
+            get_matchings(&participants, flow_network, false, &mut rand::thread_rng()).unwrap();
 
         assert_eq!(assignments.len(), participants.len());
 
@@ -359,8 +548,51 @@ mod tests {
 
         let flow_network =
             construct_flow_network(&participants, &cannot_send_to, &cannot_receive_from);
-        let problematic_nodes = get_matchings(&participants, flow_network, false).unwrap_err();
+        let problematic_nodes =
+            get_matchings(&participants, flow_network, false, &mut rand::thread_rng()).unwrap_err();
 
         assert!(problematic_nodes.len() == 1);
     }
+
+    #[test]
+    fn test_multi_assignments() {
+        let (p1, p2, p3) = get_test_participants();
+        let participants: Vec<Rc<Participant>> = vec![p1, p2, p3];
+        let empty = || {
+            participants
+                .iter()
+                .map(|participant| (participant.clone(), HashSet::new()))
+                .collect()
+        };
+        let configuration = Configuration {
+            participants: participants.iter().cloned().collect(),
+            cannot_send_to: empty(),
+            cannot_receive_from: empty(),
+        };
+
+        // With three people, everyone sends to and receives from both others
+        let assignments =
+            try_generate_multi_assignments(&configuration, |_| 2, |_| 2, &mut rand::thread_rng())
+                .unwrap();
+        assert_eq!(assignments.len(), 6);
+        assert!(assignments
+            .iter()
+            .all(|assignment| assignment.sender != assignment.recipient));
+
+        // Nobody can receive twice from the same santa
+        assert!(try_generate_multi_assignments(
+            &configuration,
+            |_| 3,
+            |_| 3,
+            &mut rand::thread_rng()
+        )
+        .is_err());
+        assert!(try_generate_multi_assignments(
+            &configuration,
+            |_| 2,
+            |_| 1,
+            &mut rand::thread_rng()
+        )
+        .is_err());
+    }
 }
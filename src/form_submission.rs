@@ -0,0 +1,131 @@
+//! Responses to the sign-up form, as exported to a `.csv` file (or stored in an event workspace),
+//! and how they turn into a [`Configuration`].
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::{Configuration, Participant};
+use crate::seed::SeedDerivation;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormSubmission {
+    #[serde(rename = "Timestamp")]
+    pub timestamp: String,
+    #[serde(rename = "Who are you?")]
+    pub name: String,
+    #[serde(rename = "Your Discord Handle")]
+    pub discord_handle: String,
+    #[serde(
+        rename = "Sender Exclusions",
+        serialize_with = "serialize_vec_string",
+        deserialize_with = "deserialize_vec_string"
+    )]
+    pub cannot_send_to_submitter: Vec<String>,
+    #[serde(
+        rename = "Recipient Exclusions",
+        serialize_with = "serialize_vec_string",
+        deserialize_with = "deserialize_vec_string"
+    )]
+    pub cannot_receive_from_submitter: Vec<String>,
+    #[serde(rename = "Your Mailing Info")]
+    pub mailing_info: String,
+    #[serde(rename = "Interests")]
+    pub interests: String,
+    #[serde(rename = "Anything Else?")]
+    pub anything_else: String,
+    /// Optional column where participants contribute entropy to the seed of the draw
+    #[serde(rename = "Your Random String", default)]
+    pub random_string: String,
+}
+
+fn deserialize_vec_string<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let buf: String = String::deserialize(deserializer)?;
+    Ok(buf.split(", ").map(|s| s.to_string()).collect())
+}
+
+fn serialize_vec_string<S>(strings: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&strings.join(", "))
+}
+
+fn participant_from_submission(submission: &FormSubmission) -> Participant {
+    Participant {
+        name: submission.name.clone(),
+        discord_handle: submission.discord_handle.clone(),
+        mailing_info: submission.mailing_info.clone(),
+        interests: submission.interests.clone(),
+    }
+}
+
+pub fn read_submissions(file_path: &str) -> Result<Vec<FormSubmission>, csv::Error> {
+    let mut csv_reader = csv::Reader::from_path(file_path)?;
+    let submissions = csv_reader.deserialize().collect::<Result<Vec<_>, _>>()?;
+    Ok(submissions)
+}
+
+pub fn configuration_from_submissions(submissions: &[FormSubmission]) -> Configuration {
+    type ParticipantName = String;
+
+    let participant_map: HashMap<ParticipantName, Rc<Participant>> = submissions
+        .iter()
+        .map(|submission| {
+            (
+                submission.name.clone(),
+                Rc::new(participant_from_submission(submission)),
+            )
+        })
+        .collect();
+    let cannot_send_to: HashMap<Rc<Participant>, HashSet<Rc<Participant>>> = submissions
+        .iter()
+        .map(|submission| {
+            (
+                participant_map[&submission.name].clone(),
+                submission
+                    .cannot_send_to_submitter
+                    .iter()
+                    .filter_map(|name| participant_map.get(name))
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let cannot_receive_from: HashMap<Rc<Participant>, HashSet<Rc<Participant>>> = submissions
+        .iter()
+        .map(|submission| {
+            (
+                participant_map[&submission.name].clone(),
+                submission
+                    .cannot_receive_from_submitter
+                    .iter()
+                    .filter_map(|name| participant_map.get(name))
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let participants: HashSet<Rc<Participant>> = participant_map.values().map(Rc::clone).collect();
+
+    Configuration {
+        participants,
+        cannot_send_to,
+        cannot_receive_from,
+    }
+}
+
+pub fn seed_derivation_from_submissions(submissions: &[FormSubmission]) -> SeedDerivation {
+    SeedDerivation::from_contributions(
+        submissions
+            .iter()
+            .map(|submission| (submission.name.clone(), submission.random_string.clone()))
+            .collect(),
+    )
+}
@@ -0,0 +1,314 @@
+//! Writes the flow network to files, before solving (every allowed pair) and after (the pairs that were drawn),
+//! in Graphviz DOT, GraphML or JSON.
+//!
+//! Anonymized exports replace every name by an opaque ID, a hash of the name with a secret random salt, so that the
+//! structure can be shared for debugging without anyone being able to check a guessed name against an ID.
+//! A workspace keeps its salt, so that the same person gets the same ID in every export of the event.
+//! Nodes and edges are sorted by ID, so that their order doesn't give away the alphabetical order of the names.
+
+use std::{fmt::Write, fs};
+
+use clap::ValueEnum;
+use petgraph::graph::DiGraph;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{flow_network::NodeLabel, server::escape_html};
+
+const ANONYMIZATION_DOMAIN_SEPARATOR: &[u8] = b"secret-santa-anonymous-id-v1";
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Graphml,
+    Json,
+}
+
+impl GraphFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Graphml => "graphml",
+            GraphFormat::Json => "json",
+        }
+    }
+}
+
+/// A new secret salt for anonymous IDs.
+pub fn random_salt() -> String {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    hex::encode(salt)
+}
+
+/// An opaque ID for a participant, the same for every export with the same salt.
+pub fn anonymous_id(salt: &str, name: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(ANONYMIZATION_DOMAIN_SEPARATOR);
+    hasher.update((salt.len() as u64).to_be_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.update(name.as_bytes());
+    format!("p{}", &hex::encode(hasher.finalize())[..10])
+}
+
+#[derive(Debug, Serialize)]
+struct ExportNode {
+    id: String,
+    kind: &'static str,
+    label: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportEdge {
+    source: String,
+    target: String,
+    capacity: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportGraph {
+    nodes: Vec<ExportNode>,
+    edges: Vec<ExportEdge>,
+}
+
+fn export_node(label: &NodeLabel, anonymization_salt: Option<&str>) -> ExportNode {
+    let name = |participant_name: &str| match anonymization_salt {
+        Some(salt) => anonymous_id(salt, participant_name),
+        None => participant_name.to_string(),
+    };
+    match label {
+        NodeLabel::Source => ExportNode {
+            id: "source".to_string(),
+            kind: "source",
+            label: "Source".to_string(),
+        },
+        NodeLabel::Sink => ExportNode {
+            id: "sink".to_string(),
+            kind: "sink",
+            label: "Sink".to_string(),
+        },
+        NodeLabel::Sender(participant) => ExportNode {
+            id: format!("sender:{}", name(&participant.name)),
+            kind: "sender",
+            label: name(&participant.name),
+        },
+        NodeLabel::Receiver(participant) => ExportNode {
+            id: format!("receiver:{}", name(&participant.name)),
+            kind: "receiver",
+            label: name(&participant.name),
+        },
+    }
+}
+
+/// Anonymized with the salt, if there is one.
+fn export_graph(
+    graph: &DiGraph<NodeLabel, usize>,
+    anonymization_salt: Option<&str>,
+) -> ExportGraph {
+    let mut nodes: Vec<ExportNode> = graph
+        .node_indices()
+        .map(|node| export_node(&graph[node], anonymization_salt))
+        .collect();
+    let mut edges: Vec<ExportEdge> = graph
+        .raw_edges()
+        .iter()
+        .map(|edge| ExportEdge {
+            source: export_node(&graph[edge.source()], anonymization_salt).id,
+            target: export_node(&graph[edge.target()], anonymization_salt).id,
+            capacity: edge.weight,
+        })
+        .collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+    ExportGraph { nodes, edges }
+}
+
+impl ExportGraph {
+    fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph {{").unwrap();
+        writeln!(dot, "    rankdir=LR;").unwrap();
+        for node in self.nodes.iter() {
+            writeln!(
+                dot,
+                "    {:?} [label={:?}, group={:?}];",
+                node.id, node.label, node.kind
+            )
+            .unwrap();
+        }
+        for edge in self.edges.iter() {
+            writeln!(
+                dot,
+                "    {:?} -> {:?} [label=\"{}\"];",
+                edge.source, edge.target, edge.capacity
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    fn to_graphml(&self) -> String {
+        let mut graphml = String::new();
+        writeln!(graphml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            graphml,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )
+        .unwrap();
+        writeln!(
+            graphml,
+            r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+        )
+        .unwrap();
+        writeln!(
+            graphml,
+            r#"  <key id="kind" for="node" attr.name="kind" attr.type="string"/>"#
+        )
+        .unwrap();
+        writeln!(
+            graphml,
+            r#"  <key id="capacity" for="edge" attr.name="capacity" attr.type="int"/>"#
+        )
+        .unwrap();
+        writeln!(graphml, r#"  <graph id="G" edgedefault="directed">"#).unwrap();
+        for node in self.nodes.iter() {
+            writeln!(
+                graphml,
+                r#"    <node id="{}"><data key="label">{}</data><data key="kind">{}</data></node>"#,
+                escape_html(&node.id),
+                escape_html(&node.label),
+                node.kind
+            )
+            .unwrap();
+        }
+        for edge in self.edges.iter() {
+            writeln!(
+                graphml,
+                r#"    <edge source="{}" target="{}"><data key="capacity">{}</data></edge>"#,
+                escape_html(&edge.source),
+                escape_html(&edge.target),
+                edge.capacity
+            )
+            .unwrap();
+        }
+        writeln!(graphml, "  </graph>").unwrap();
+        writeln!(graphml, "</graphml>").unwrap();
+        graphml
+    }
+
+    fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Graphml => self.to_graphml(),
+            GraphFormat::Json => serde_json::to_string_pretty(self).unwrap(),
+        }
+    }
+}
+
+/// Writes `<directory>/<base_name>[_anonymized].<extension>` for every format and returns the paths that were written.
+/// Names are anonymized with the salt, if there is one.
+pub fn write_graph(
+    graph: &DiGraph<NodeLabel, usize>,
+    directory: &str,
+    base_name: &str,
+    formats: &[GraphFormat],
+    anonymization_salt: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let export = export_graph(graph, anonymization_salt);
+    formats
+        .iter()
+        .map(|format| {
+            let file_path = format!(
+                "{}/{}{}.{}",
+                directory,
+                base_name,
+                if anonymization_salt.is_some() {
+                    "_anonymized"
+                } else {
+                    ""
+                },
+                format.extension()
+            );
+            fs::write(&file_path, export.render(*format))
+                .map_err(|error| format!("Failed to write {}: {}", file_path, error))?;
+            Ok(file_path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::configuration::Participant;
+
+    fn get_test_graph() -> DiGraph<NodeLabel, usize> {
+        let alice = Rc::new(Participant {
+            name: "Alice".to_string(),
+            discord_handle: "alice#1234".to_string(),
+            mailing_info: "1234 Alice Lane".to_string(),
+            interests: "Programming, cats".to_string(),
+        });
+        let mut graph = DiGraph::new();
+        let source = graph.add_node(NodeLabel::Source);
+        let sender = graph.add_node(NodeLabel::Sender(alice.clone()));
+        let receiver = graph.add_node(NodeLabel::Receiver(alice));
+        graph.add_edge(source, sender, 1);
+        graph.add_edge(sender, receiver, 1);
+        graph
+    }
+
+    #[test]
+    fn test_anonymous_id_is_stable() {
+        let salt = random_salt();
+        assert_eq!(anonymous_id(&salt, "Alice"), anonymous_id(&salt, "Alice"));
+        assert_ne!(anonymous_id(&salt, "Alice"), anonymous_id(&salt, "Bob"));
+        assert_eq!(anonymous_id(&salt, "Alice").len(), 11);
+    }
+
+    #[test]
+    fn test_anonymous_id_depends_on_the_salt() {
+        let salt = random_salt();
+        assert_ne!(
+            anonymous_id(&salt, "Alice"),
+            anonymous_id(&random_salt(), "Alice")
+        );
+
+        // Hashing a guessed name, with or without the domain separator, doesn't give the ID away
+        let unsalted = [
+            hex::encode(Sha256::digest(b"Alice")),
+            hex::encode(Sha256::digest(
+                [ANONYMIZATION_DOMAIN_SEPARATOR, b"Alice"].concat(),
+            )),
+        ];
+        for hash in unsalted {
+            assert_ne!(anonymous_id(&salt, "Alice"), format!("p{}", &hash[..10]));
+        }
+    }
+
+    #[test]
+    fn test_anonymized_exports_hide_names() {
+        let salt = random_salt();
+        let export = export_graph(&get_test_graph(), Some(&salt));
+
+        for format in [GraphFormat::Dot, GraphFormat::Graphml, GraphFormat::Json] {
+            let rendered = export.render(format);
+            assert!(!rendered.contains("Alice"));
+            assert!(rendered.contains(&anonymous_id(&salt, "Alice")));
+        }
+    }
+
+    #[test]
+    fn test_named_export() {
+        let export = export_graph(&get_test_graph(), None);
+
+        assert_eq!(export.nodes.len(), 3);
+        assert_eq!(export.edges.len(), 2);
+        assert!(export
+            .render(GraphFormat::Dot)
+            .contains(r#""sender:Alice" -> "receiver:Alice""#));
+    }
+}
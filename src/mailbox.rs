@@ -0,0 +1,250 @@
+//! An anonymous mailbox between every santa and their recipient, so that a santa can ask "what size?"
+//! without giving themselves away.
+//!
+//! Messages are stored in the workspace and keyed by the pair, not by the draw, so a conversation survives
+//! repairs and insertions that keep the pair. A recipient only ever sees "Your Secret Santa" as the author.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::workspace::{DrawRecord, Workspace};
+
+/// Longest message that is accepted, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// One side of a santa/recipient pair, as seen from the other side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Correspondent {
+    Santa,
+    Recipient,
+}
+
+impl Correspondent {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Correspondent::Santa => "Your Secret Santa",
+            Correspondent::Recipient => "Your recipient",
+        }
+    }
+
+    /// The label in the middle of a sentence.
+    pub fn noun(&self) -> &'static str {
+        match self {
+            Correspondent::Santa => "your Secret Santa",
+            Correspondent::Recipient => "your recipient",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Message {
+    pub santa: String,
+    pub recipient: String,
+    /// Who wrote the message
+    pub from: Correspondent,
+    pub text: String,
+    pub at: String,
+}
+
+/// The messages between a participant and their santa or recipient, oldest first.
+pub struct Thread {
+    pub with: Correspondent,
+    pub messages: Vec<Message>,
+}
+
+impl Thread {
+    /// Who wrote the message, from the point of view of the participant reading the thread.
+    pub fn author_label(&self, message: &Message) -> &'static str {
+        if message.from == self.with {
+            self.with.label()
+        } else {
+            "You"
+        }
+    }
+
+    pub fn to_human(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "Messages with {}:", self.with.noun()).unwrap();
+        if self.messages.is_empty() {
+            writeln!(text, "  (none yet)").unwrap();
+        }
+        for message in self.messages.iter() {
+            writeln!(
+                text,
+                "  [{}] {}: {}",
+                message.at,
+                self.author_label(message),
+                message.text
+            )
+            .unwrap();
+        }
+        text
+    }
+}
+
+/// The (santa, recipient) pair the participant writes to the correspondent in.
+fn pair(
+    draw: &DrawRecord,
+    participant: &str,
+    correspondent: Correspondent,
+) -> Result<(String, String), String> {
+    let not_in_draw = || format!("{:?} isn't part of draw #{}", participant, draw.id);
+    Ok(match correspondent {
+        Correspondent::Recipient => (
+            participant.to_string(),
+            draw.recipient_of(participant)
+                .ok_or_else(not_in_draw)?
+                .to_string(),
+        ),
+        Correspondent::Santa => (
+            draw.santa_of(participant)
+                .ok_or_else(not_in_draw)?
+                .to_string(),
+            participant.to_string(),
+        ),
+    })
+}
+
+/// Both threads of the participant in the draw: with their recipient and with their santa.
+pub fn threads(
+    draw: &DrawRecord,
+    messages: &[Message],
+    participant: &str,
+) -> Result<Vec<Thread>, String> {
+    [Correspondent::Recipient, Correspondent::Santa]
+        .into_iter()
+        .map(|with| {
+            let (santa, recipient) = pair(draw, participant, with)?;
+            Ok(Thread {
+                with,
+                messages: messages
+                    .iter()
+                    .filter(|message| message.santa == santa && message.recipient == recipient)
+                    .cloned()
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Stores a message from the participant to their santa or recipient in the latest draw. Doesn't save the workspace.
+pub fn post(
+    workspace: &mut Workspace,
+    participant: &str,
+    to: Correspondent,
+    text: &str,
+) -> Result<(), String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("The message is empty".to_string());
+    }
+    if text.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(format!(
+            "Messages can be at most {} characters long",
+            MAX_MESSAGE_LENGTH
+        ));
+    }
+    let (santa, recipient) = pair(workspace.draw(None)?, participant, to)?;
+    workspace.state.messages.push(Message {
+        santa,
+        recipient,
+        from: match to {
+            Correspondent::Santa => Correspondent::Recipient,
+            Correspondent::Recipient => Correspondent::Santa,
+        },
+        text: text.to_string(),
+        at: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+    });
+    // Only name the author, the log must not give the pair away
+    workspace.log(format!(
+        "{} sent a message to {}",
+        participant,
+        match to {
+            Correspondent::Santa => "their Secret Santa",
+            Correspondent::Recipient => "their recipient",
+        }
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        commitment::{Recipe, Reveal, RevealedAssignment},
+        workspace::DrawKind,
+    };
+
+    fn get_test_draw() -> DrawRecord {
+        let reveal = Reveal {
+            seed: String::new(),
+            recipe: Recipe::Draw {
+                method: "flow-network".to_string(),
+            },
+            assignments: [("Alice", "Bob"), ("Bob", "Charlie"), ("Charlie", "Alice")]
+                .into_iter()
+                .map(|(sender, recipient)| RevealedAssignment {
+                    sender: sender.to_string(),
+                    recipient: recipient.to_string(),
+                    salt: String::new(),
+                })
+                .collect(),
+        };
+        DrawRecord {
+            id: 1,
+            kind: DrawKind::Draw,
+            parent: None,
+            created_at: String::new(),
+            method: None,
+            commitment: reveal.commitment(),
+            reveal,
+            seed_derivation: None,
+            deliveries: BTreeMap::new(),
+            portal_views: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_threads_hide_the_santa() {
+        let draw = get_test_draw();
+        let question = Message {
+            santa: "Alice".to_string(),
+            recipient: "Bob".to_string(),
+            from: Correspondent::Santa,
+            text: "What size?".to_string(),
+            at: String::new(),
+        };
+
+        let messages = vec![question.clone()];
+
+        let bob = threads(&draw, &messages, "Bob").unwrap();
+        let from_santa = bob
+            .iter()
+            .find(|thread| thread.with == Correspondent::Santa)
+            .unwrap();
+        assert_eq!(from_santa.messages, vec![question]);
+        assert!(!from_santa.to_human().contains("Alice"));
+        assert!(from_santa
+            .to_human()
+            .contains("Your Secret Santa: What size?"));
+
+        let alice = threads(&draw, &messages, "Alice").unwrap();
+        let to_recipient = alice
+            .iter()
+            .find(|thread| thread.with == Correspondent::Recipient)
+            .unwrap();
+        assert!(to_recipient.to_human().contains("You: What size?"));
+
+        // Charlie isn't part of the pair
+        assert!(threads(&draw, &messages, "Charlie")
+            .unwrap()
+            .iter()
+            .all(|thread| thread.messages.is_empty()));
+        assert!(threads(&draw, &[], "Eve").is_err());
+    }
+}
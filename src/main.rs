@@ -1,243 +1,678 @@
-use rand::rngs::ThreadRng;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fs;
-use std::iter::zip;
+use std::path::Path;
 use std::rc::Rc;
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
+mod analysis;
+mod api;
+mod bias;
+mod commitment;
 mod configuration;
+mod counting;
+mod derangement;
+mod draw_statistics;
+mod editor;
 mod flow_network;
+mod form_submission;
+mod graph_export;
+mod mailbox;
+mod matching;
 mod permutation;
+mod portal;
 mod random_ford_fulkerson;
+mod reissue;
+mod repair;
+mod replay;
+mod rounds;
+mod run_report;
+mod seed;
+mod server;
+mod swaps;
+mod teams;
+mod uniform;
+mod validation;
+mod visualization;
+mod workspace;
 
+use crate::commitment::{InclusionProof, Recipe, Reveal};
 use crate::configuration::{Configuration, Participant};
+use crate::draw_statistics::DrawStatistics;
+use crate::form_submission::{
+    configuration_from_submissions, read_submissions, seed_derivation_from_submissions,
+    FormSubmission,
+};
+use crate::graph_export::GraphFormat;
+use crate::mailbox::Correspondent;
+use crate::matching::{generate_permutation, MatchingMethod, PermutationBudget};
 use crate::permutation::{Assignment, Permutation};
+use crate::run_report::{RunReport, SeedSource};
+use crate::seed::{Seed, SeedDerivation};
+use crate::visualization::{RingOptions, VisualizationFormat};
+use crate::workspace::{DeliveryStatus, DrawKind, Workspace};
 
 #[derive(Clone, Debug, ValueEnum)]
-enum MatchingMethod {
-    Permutation,
-    FlowNetwork,
+enum ReportFormat {
+    Human,
+    Json,
 }
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    draw_arguments: DrawArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create an event workspace that stores the roster, every draw, delivery status and history.
+    Init(InitArgs),
+    /// Add new sign-ups from a responses file to an event workspace's roster.
+    Import(ImportArgs),
+    /// Check the sign-up responses for mistakes and make sure a valid assignment exists, without drawing or writing any files.
+    Validate(ValidateArgs),
+    /// Find the pairings anyone who knows the exclusions can deduce, and participants with very few possible recipients or senders. The output reveals those pairings!
+    Analyze(AnalyzeArgs),
+    /// Count the valid assignments, i.e. how much randomness a draw has.
+    Stats(StatsArgs),
+    /// Write the flow network of allowed pairs (and optionally of a stored draw) to files, e.g. to share it for debugging.
+    ExportGraph(ExportGraphArgs),
+    /// Run matching methods many times and compare how often each pair comes up with a uniform draw.
+    BiasReport(BiasReportArgs),
+    /// Show the roster, draws, delivery status and history of an event workspace.
+    Status(WorkspaceArgs),
+    /// Print every pairing of a stored draw. Only do this once the exchange is over!
+    Reveal(RevealArgs),
+    /// Draw a stored draw as rings of names for the reveal party. Only do this once the exchange is over!
+    Visualize(VisualizeArgs),
+    /// Write one participant's file again from a stored draw, without showing their pairing.
+    Reissue(ReissueArgs),
+    /// Check a revealed draw against the commitment published before delivery and against every exclusion constraint.
+    Verify(VerifyArgs),
+    /// Check that your own assignment is part of a published draw, using the proof from your file.
+    VerifyProof(VerifyProofArgs),
+    /// Repair a previous draw after participants drop out, changing as few existing pairs as possible.
+    Repair(RepairArgs),
+    /// Splice late joiners into a previous draw, only changing one existing pair per joiner.
+    Insert(InsertArgs),
+    /// Host a sign-up form that adds sign-ups to an event workspace, until the sign-up deadline.
+    Serve(ServeArgs),
+    /// Issue every participant of the latest draw a secret link to the participant portal and print the links.
+    PortalLinks(PortalLinksArgs),
+    /// Host the participant portal, where everyone looks up their own assignment through their secret link.
+    Portal(PortalArgs),
+    /// Send an anonymous message to your recipient, or reply to your Secret Santa.
+    Message(MessageArgs),
+    /// Show a participant's messages with their recipient and their Secret Santa.
+    Inbox(InboxArgs),
+    /// Serve a JSON HTTP API with POST /match, /validate and /explain, for bots and other programs.
+    Api(ApiArgs),
+    /// Edit the exclusions of an event workspace in an interactive terminal UI, with live feasibility checks.
+    Edit(WorkspaceArgs),
+    /// Draw several gifts per person, where nobody receives two gifts from the same santa.
+    MultiGift(MultiGiftArgs),
+    /// Draw several rounds of gifts where no santa gets the same recipient twice, e.g. for an advent event.
+    Rounds(RoundsArgs),
+    /// Pair everyone up with a partner to swap gifts with, plus one group of three if the number of participants is odd.
+    Swaps(SwapsArgs),
+    /// Split everyone into santa teams of two or three that each give one recipient a bigger gift together.
+    ///
+    /// There are fewer teams than people, so only as many participants receive a gift as there are teams; the others are listed at the end.
+    Teams(TeamsArgs),
+}
+
+// Performs a draw and writes one file per participant. This is what runs when no subcommand is given.
+#[derive(clap::Args, Debug)]
+struct DrawArgs {
     /// Input file path
     #[arg(short, long, default_value = "./input_data.csv")]
     input_file_path: String,
 
-    /// Output directory path
-    #[arg(short, long, default_value = "./matchings")]
-    output_directory_path: String,
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
 
-    /// Matching method. "flow-network" is recommended, as it will terminate if a valid assignment cannot be found, unlike "permutation".
+    /// Matching method. "flow-network" is recommended. "permutation" tries random derangements until one satisfies the exclusions, which can take very long when they are tight. "uniform" makes every valid assignment equally likely.
     #[arg(short, long, value_enum, default_value_t = MatchingMethod::FlowNetwork)]
     matching_method: MatchingMethod,
 
+    /// Give up on the "permutation" method after this many attempts
+    #[arg(long, default_value_t = PermutationBudget::default().max_attempts)]
+    max_attempts: u64,
+
+    /// Give up on the "permutation" method after this many seconds
+    #[arg(long, default_value_t = PermutationBudget::default().time_limit.as_secs())]
+    time_limit: u64,
+
     /// Verbose flag.
     #[arg(short = 'v', long = "verbose", default_value = "false")]
     do_be_verbose: bool,
+
+    /// Hex-encoded seed to rerun a previous draw exactly. By default the seed is derived from the participants' random strings.
+    #[arg(short, long)]
+    seed: Option<String>,
+
+    /// Event workspace to take the roster from and store the draw in, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Also write the flow network before and after the draw to the output directory in this format. Can be given multiple times.
+    #[arg(short, long = "graph-format", value_enum)]
+    graph_formats: Vec<GraphFormat>,
+
+    /// Replace names by opaque IDs in the graph files, which stay the same across the exports of a workspace
+    #[arg(long, requires = "graph_formats")]
+    anonymize_graphs: bool,
+
+    /// "json" prints a single report to stdout instead of the progress messages
+    #[arg(long, value_enum, default_value_t = ReportFormat::Human)]
+    output_format: ReportFormat,
+
+    /// Include every pairing in the JSON report. Only use this when nobody will read the report!
+    #[arg(long)]
+    include_pairings: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct WorkspaceArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct FormSubmission {
-    #[serde(rename = "Timestamp")]
-    _timestamp: String,
-    #[serde(rename = "Who are you?")]
+#[derive(clap::Args, Debug)]
+struct InitArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
+
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Name of the event
+    #[arg(short, long, default_value = "Secret Santa")]
     name: String,
-    #[serde(rename = "Your Discord Handle")]
-    discord_handle: String,
-    #[serde(
-        rename = "Sender Exclusions",
-        deserialize_with = "deserialize_vec_string"
-    )]
-    cannot_send_to_submitter: Vec<String>,
-    #[serde(
-        rename = "Recipient Exclusions",
-        deserialize_with = "deserialize_vec_string"
-    )]
-    cannot_receive_from_submitter: Vec<String>,
-    #[serde(rename = "Your Mailing Info")]
-    mailing_info: String,
-    #[serde(rename = "Interests")]
-    interests: String,
-    #[serde(rename = "Anything Else?")]
-    _anything_else: String,
-}
-
-fn deserialize_vec_string<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let buf: String = String::deserialize(deserializer)?;
-    Ok(buf.split(", ").map(|s| s.to_string()).collect())
-}
-
-fn participant_from_submission(submission: &FormSubmission) -> Participant {
-    Participant {
-        name: submission.name.clone(),
-        discord_handle: submission.discord_handle.clone(),
-        mailing_info: submission.mailing_info.clone(),
-        interests: submission.interests.clone(),
-    }
 }
 
-fn read_configuration_from_csv(file_path: &str) -> Configuration {
-    // Read the CSV file at the given path and return the Configuration (participants and exclusion constraints)
+#[derive(clap::Args, Debug)]
+struct ImportArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-    fn read_submissions(file_path: &str) -> Result<Vec<FormSubmission>, csv::Error> {
-        let mut csv_reader = csv::Reader::from_path(file_path)?;
-        let submissions = csv_reader.deserialize().collect::<Result<Vec<_>, _>>()?;
-        Ok(submissions)
-    }
-    let submissions = read_submissions(file_path).unwrap();
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+}
 
-    type ParticipantName = String;
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-    let participant_map: HashMap<ParticipantName, Rc<Participant>> = submissions
-        .iter()
-        .map(|submission| {
-            (
-                submission.name.clone(),
-                Rc::new(participant_from_submission(submission)),
-            )
-        })
-        .collect();
-    let cannot_send_to: HashMap<Rc<Participant>, HashSet<Rc<Participant>>> = submissions
-        .iter()
-        .map(|submission| {
-            (
-                participant_map[&submission.name].clone(),
-                submission
-                    .cannot_send_to_submitter
-                    .iter()
-                    .filter_map(|name| participant_map.get(name))
-                    .cloned()
-                    .collect(),
-            )
-        })
-        .collect();
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    address: String,
 
-    let cannot_receive_from: HashMap<Rc<Participant>, HashSet<Rc<Participant>>> = submissions
-        .iter()
-        .map(|submission| {
-            (
-                participant_map[&submission.name].clone(),
-                submission
-                    .cannot_receive_from_submitter
-                    .iter()
-                    .filter_map(|name| participant_map.get(name))
-                    .cloned()
-                    .collect(),
-            )
-        })
-        .collect();
+    /// Stop accepting sign-ups after this time, as RFC 3339 or local "YYYY-MM-DD HH:MM". Stored in the workspace.
+    #[arg(short, long)]
+    deadline: Option<String>,
+}
 
-    let participants: HashSet<Rc<Participant>> = participant_map.values().map(Rc::clone).collect();
+#[derive(clap::Args, Debug)]
+struct PortalLinksArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-    Configuration {
-        participants,
-        cannot_send_to,
-        cannot_receive_from,
-    }
+    /// Address participants reach the portal at
+    #[arg(short, long, default_value = "http://127.0.0.1:8080")]
+    base_url: String,
 }
 
-fn generate_valid_permutation(
-    configuration: Configuration,
-    do_be_verbose: bool,
-) -> Permutation<Rc<Participant>> {
-    // Repeatedly try different derangements until we find one that satisfies the exclusion constraints
-
-    // We have an n x n matrix (where n is the number of participants)
-    // A possible permutation is a matrix that has exactly one 1 in each row and each column
-    // A derangement is a permutation where there are no 1s on the diagonal
-
-    // Generate random permutation matrices and test them until we find one that is 1. a derangement and 2. satisfies exclusion constraints
-
-    let mut rng = thread_rng();
-
-    fn gen_iter(
-        rng: &mut ThreadRng,
-        configuration: &Configuration,
-    ) -> Result<Permutation<Rc<Participant>>, String> {
-        let participants_randomized = {
-            let mut participants: Vec<&Rc<Participant>> =
-                Vec::from_iter(configuration.participants.iter());
-            participants.shuffle(rng);
-            participants
-        };
-        let random_assignments = zip(configuration.participants.iter(), participants_randomized)
-            .map(|(p1, p2)| Assignment {
-                sender: p1.clone(),
-                recipient: p2.clone(),
-            })
-            .collect();
+#[derive(clap::Args, Debug)]
+struct PortalArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-        let permutation = Permutation::try_new(random_assignments, &configuration.participants)?;
-        configuration.ensure_valid_permutation(&permutation)?;
-        Ok(permutation)
-    }
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    address: String,
+}
 
-    let mut loop_count: u128 = 0;
+#[derive(clap::Args, Debug)]
+struct MessageArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-    loop {
-        loop_count += 1;
-        if do_be_verbose {
-            eprintln!("Trying permutation #{}:", loop_count)
-        };
+    /// Name of the participant sending the message
+    #[arg(short, long)]
+    from: String,
 
-        match gen_iter(&mut rng, &configuration) {
-            Err(message) => {
-                if do_be_verbose {
-                    eprintln!("{}", message)
-                }
-            }
-            Ok(permutation) => return permutation,
-        }
-    }
+    /// Who to send the message to
+    #[arg(short, long, value_enum)]
+    to: Correspondent,
+
+    /// The message
+    text: String,
 }
 
-fn try_generate_assignments_via_flow_network(
-    configuration: Configuration,
-    be_verbose: bool,
-) -> Result<HashSet<Assignment<Rc<Participant>>>, String> {
-    let flow_network = flow_network::construct_flow_network(
-        &configuration.participants,
-        &configuration.cannot_send_to,
-        &configuration.cannot_receive_from,
-    );
+#[derive(clap::Args, Debug)]
+struct InboxArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
 
-    flow_network::get_matchings(&configuration.participants, flow_network, be_verbose).map_err(
-        |problematic_nodes| {
-            format!(
-                "Failed to find a valid assignment: {}",
-                problematic_nodes
-                    .into_iter()
-                    .filter_map(|p| {
-                        match p {
-                            flow_network::NodeLabel::Sender(p) => {
-                                Some(format!("{} is unable to send to anyone", p.name))
-                            }
-                            flow_network::NodeLabel::Receiver(p) => {
-                                Some(format!("{} is unable to receive from anyone", p.name))
-                            }
-                            _ => None,
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )
-        },
-    )
+    /// Name of the participant whose messages should be shown
+    name: String,
 }
 
-fn write_matching_files(
-    assignments: HashSet<Assignment<Rc<Participant>>>,
-    output_directory: &str,
-) -> String {
+#[derive(clap::Args, Debug)]
+struct ApiArgs {
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8081")]
+    address: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct MultiGiftArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file. The draw isn't stored in it.
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+
+    /// Hex-encoded seed to rerun a previous draw exactly. By default the seed is derived from the participants' random strings.
+    #[arg(short, long)]
+    seed: Option<String>,
+
+    /// How many gifts everyone sends and receives
+    #[arg(short = 'k', long, default_value_t = 2)]
+    gifts_per_person: usize,
+
+    /// Let someone send a different number of gifts, e.g. "Alice Smith=3". Can be given multiple times.
+    #[arg(long, value_parser = parse_capacity)]
+    send_capacity: Vec<(String, usize)>,
+
+    /// Let someone receive a different number of gifts, e.g. "Bob Jones=3". Can be given multiple times.
+    #[arg(long, value_parser = parse_capacity)]
+    receive_capacity: Vec<(String, usize)>,
+}
+
+#[derive(clap::Args, Debug)]
+struct RoundsArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from and store every round in as a draw, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+
+    /// Hex-encoded seed to rerun previous rounds exactly. By default the seed is derived from the participants' random strings.
+    #[arg(short, long)]
+    seed: Option<String>,
+
+    /// How many rounds to draw
+    #[arg(short, long)]
+    rounds: usize,
+
+    /// Smallest number of people in a gift-giving cycle within a round
+    #[arg(long, default_value_t = 2)]
+    min_cycle_length: usize,
+
+    /// Don't let two people send to each other in the same round
+    #[arg(long)]
+    no_mutual: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct SwapsArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file. The swaps aren't stored in it.
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+
+    /// Hex-encoded seed to rerun previous swaps exactly. By default the seed is derived from the participants' random strings.
+    #[arg(short, long)]
+    seed: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct TeamsArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file. The teams aren't stored in it.
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+
+    /// Hex-encoded seed to rerun a previous draw exactly. By default the seed is derived from the participants' random strings.
+    #[arg(short, long)]
+    seed: Option<String>,
+
+    /// How many santas are on a team. Teams of the other size make up the rest.
+    #[arg(short = 'k', long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(2..=3))]
+    team_size: u8,
+}
+
+/// Parses "<name>=<count>".
+fn parse_capacity(capacity: &str) -> Result<(String, usize), String> {
+    let (name, count) = capacity
+        .rsplit_once('=')
+        .ok_or_else(|| format!("Expected <name>=<count>, got {:?}", capacity))?;
+    let count = count
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid count {:?}", count))?;
+    Ok((name.trim().to_string(), count))
+}
+
+#[derive(clap::Args, Debug)]
+struct ValidateArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Warn about participants who can send to or receive from at most this many people
+    #[arg(short = 't', long, default_value_t = 2)]
+    few_eligible_threshold: usize,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Human)]
+    format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct AnalyzeArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Warn about participants with at most this many possible recipients or senders
+    #[arg(short = 't', long, default_value_t = 1)]
+    low_entropy_threshold: usize,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Human)]
+    format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Samples for estimating the count of groups too large to count exactly
+    #[arg(short, long, default_value_t = counting::DEFAULT_SAMPLES)]
+    samples: usize,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Human)]
+    format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExportGraphArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Id of a stored draw whose flow should be written as well
+    #[arg(short, long, requires = "workspace")]
+    draw: Option<usize>,
+
+    /// Output directory path
+    #[arg(short, long, default_value = ".")]
+    output_directory_path: String,
+
+    /// Graph format. Can be given multiple times.
+    #[arg(short, long = "graph-format", value_enum, required = true)]
+    graph_formats: Vec<GraphFormat>,
+
+    /// Replace names by opaque IDs, which stay the same across the exports of a workspace
+    #[arg(long)]
+    anonymize: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct BiasReportArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Event workspace to take the roster from, instead of the input file
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Matching method to measure. Can be given multiple times. [default: every method]
+    #[arg(short, long = "matching-method", value_enum)]
+    matching_methods: Vec<MatchingMethod>,
+
+    /// How many times to run each method
+    #[arg(short = 'n', long, default_value_t = 2000)]
+    runs: usize,
+
+    /// How many of the most biased pairs to show per method
+    #[arg(short = 'p', long, default_value_t = 5)]
+    shown_pairs: usize,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Human)]
+    format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct RevealArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
+
+    /// Id of the draw to reveal [default: the latest draw]
+    #[arg(short, long)]
+    draw: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct VisualizeArgs {
+    /// Event workspace to take the draw from
+    #[arg(short, long, required_unless_present = "reveal_file_path")]
+    workspace: Option<String>,
+
+    /// Id of the draw to visualize [default: the latest draw]
+    #[arg(short, long, requires = "workspace")]
+    draw: Option<usize>,
+
+    /// Path to a reveal.json to visualize, instead of a stored draw
+    #[arg(short, long, conflicts_with = "workspace")]
+    reveal_file_path: Option<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = VisualizationFormat::Html)]
+    format: VisualizationFormat,
+
+    /// Output file path [default: ./gift_graph.<format>]
+    #[arg(short, long)]
+    output_file_path: Option<String>,
+
+    /// Only show first names
+    #[arg(long)]
+    hide_surnames: bool,
+
+    /// Highlight the longest chain
+    #[arg(long)]
+    highlight_longest: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReissueArgs {
+    /// Event workspace directory
+    #[arg(short, long)]
+    workspace: String,
+
+    /// Name of the participant whose file should be written again
+    name: String,
+
+    /// Id of the draw to reissue from [default: the latest draw]
+    #[arg(short, long)]
+    draw: Option<usize>,
+
+    /// Output directory path [default: <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Input file path
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// The commitment that was published before the files were delivered [default with --workspace: the stored commitment]
+    #[arg(short, long, required_unless_present = "workspace")]
+    commitment: Option<String>,
+
+    /// Path to the reveal.json written alongside the matching files
+    #[arg(short, long, required_unless_present = "workspace")]
+    reveal_file_path: Option<String>,
+
+    /// Path to the reveal.json of the previous draw a repaired draw (or one with late joiners) was made from, to rerun it
+    #[arg(short, long, conflicts_with = "workspace")]
+    parent_reveal_file_path: Option<String>,
+
+    /// Event workspace to take the roster and the draw from, instead of the input and reveal files
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Id of the stored draw to verify [default: the latest draw]
+    #[arg(short, long, requires = "workspace")]
+    draw: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyProofArgs {
+    /// The commitment that was published before the files were delivered
+    #[arg(short, long)]
+    commitment: String,
+
+    /// Your name
+    #[arg(short, long)]
+    sender: String,
+
+    /// The name of the person you were assigned
+    #[arg(short, long)]
+    recipient: String,
+
+    /// The proof of inclusion from your file
+    #[arg(short, long)]
+    proof: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct RepairArgs {
+    /// Input file path (the sign-up responses the previous draw was made from)
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Path to the reveal.json of the draw to repair
+    #[arg(short, long, required_unless_present = "workspace")]
+    reveal_file_path: Option<String>,
+
+    /// Event workspace whose latest draw should be repaired, instead of the input and reveal files
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Name of a participant who dropped out. Can be given multiple times.
+    #[arg(short = 'x', long = "remove", required = true)]
+    removed_names: Vec<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct InsertArgs {
+    /// Input file path (the sign-up responses, including the late joiners)
+    #[arg(short, long, default_value = "./input_data.csv")]
+    input_file_path: String,
+
+    /// Path to the reveal.json of the draw to insert into
+    #[arg(short, long, required_unless_present = "workspace")]
+    reveal_file_path: Option<String>,
+
+    /// Event workspace whose latest draw the joiners (who must be on its roster) are inserted into, instead of the input and reveal files
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Name of a participant who joined late. Can be given multiple times.
+    #[arg(short, long = "add", required = true)]
+    added_names: Vec<String>,
+
+    /// Output directory path [default: ./matchings, or <workspace>/matchings]
+    #[arg(short, long)]
+    output_directory_path: Option<String>,
+}
+
+fn read_configuration_from_csv(file_path: &str) -> Configuration {
+    // Read the CSV file at the given path and return the Configuration (participants and exclusion constraints)
+    configuration_from_submissions(&read_submissions(file_path).unwrap())
+}
+
+fn read_seed_derivation_from_csv(file_path: &str) -> SeedDerivation {
+    // Read the CSV file at the given path and derive the seed from every participant's random string
+    seed_derivation_from_submissions(&read_submissions(file_path).unwrap())
+}
+
+fn create_output_directory(output_directory: &str) -> String {
     // Create matchings directory if necessary
     if fs::create_dir(output_directory).is_err() {
         eprintln!(
@@ -259,66 +694,1599 @@ fn write_matching_files(
         );
     }
 
-    for assignment in assignments {
-        let sender = &assignment.sender;
-        let recipient = &assignment.recipient;
+    output_directory
+}
 
-        let padding_disclaimer =
-            "SCROLL DOWN TO SEE WHO YOU GOT\nTHIS IS TO HIDE IT FROM THE DISCORD EMBED\n"
-                .to_string();
-        let vertical_padding = &"|\n".repeat(25);
-        let information = &format!(
-            "You are the Secret Santa for {}! ({})\n\nAddress:\n{}\n\nTheir interests are:\n{}",
-            recipient.name, recipient.discord_handle, recipient.mailing_info, recipient.interests
-        );
-        let closing = &"\n\n\n\nRemember to check the Google Form for information about suggested price range and gift 'due date'! Happy gifting!".to_string();
+fn write_file(file_path: &str, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    fs::write(file_path, contents)
+        .map_err(|error| format!("Failed to write {}: {}", file_path, error))
+}
 
-        fs::write(
-            format!("{}/{}.txt", output_directory, sender.name),
-            padding_disclaimer + vertical_padding + information + closing,
-        )
-        .unwrap();
+fn write_draw_files(
+    reveal: &Reveal,
+    seed_derivation: Option<&SeedDerivation>,
+    output_directory: &str,
+) -> Result<(), String> {
+    write_file(
+        &format!("{}/commitment.txt", output_directory),
+        format!("{}\n", reveal.commitment()),
+    )?;
+    write_file(
+        &format!("{}/reveal.json", output_directory),
+        serde_json::to_string_pretty(reveal).unwrap(),
+    )?;
+    if let Some(seed_derivation) = seed_derivation {
+        write_file(
+            &format!("{}/seed_derivation.txt", output_directory),
+            seed_derivation.describe(),
+        )?;
     }
-
-    output_directory
+    Ok(())
 }
 
-fn main() {
-    let arguments = Args::parse();
+fn write_assignment_file(
+    assignment: &Assignment<Rc<Participant>>,
+    reveal: &Reveal,
+    output_directory: &str,
+) -> Result<String, String> {
+    let sender = &assignment.sender;
+    let recipient = &assignment.recipient;
 
-    let start_time = std::time::Instant::now();
+    let padding_disclaimer =
+        "SCROLL DOWN TO SEE WHO YOU GOT\nTHIS IS TO HIDE IT FROM THE DISCORD EMBED\n".to_string();
+    let vertical_padding = &"|\n".repeat(25);
+    let information = &format!(
+        "You are the Secret Santa for {}! ({})\n\nAddress:\n{}\n\nTheir interests are:\n{}",
+        recipient.name, recipient.discord_handle, recipient.mailing_info, recipient.interests
+    );
+    let closing = &"\n\n\n\nRemember to check the Google Form for information about suggested price range and gift 'due date'! Happy gifting!".to_string();
+    let proof = &format!(
+        "\n\n\nDraw commitment: {}\nYour proof of inclusion: {}\n(Run `secret_santa verify-proof` with these once the commitment is published to check that your assignment is part of the draw.)",
+        reveal.commitment(),
+        reveal.proof_for(&sender.name).unwrap()
+    );
 
-    eprintln!("Loading configuration...");
-    let configuration = read_configuration_from_csv(&arguments.input_file_path);
+    let file_path = format!("{}/{}.txt", output_directory, sender.file_name());
+    write_file(
+        &file_path,
+        padding_disclaimer + vertical_padding + information + closing + proof,
+    )?;
+    Ok(file_path)
+}
 
-    eprintln!("Loaded participants:");
-    for participant in configuration.participants.iter() {
-        eprintln!("{:?}", participant.name);
+/// Writes a file for a sender with several recipients, e.g. in a multi-gift draw, and returns its path.
+fn write_gift_list_file(
+    sender: &Participant,
+    heading: &str,
+    recipients: &[(String, Rc<Participant>)],
+    output_directory: &str,
+) -> Result<String, String> {
+    let padding_disclaimer =
+        "SCROLL DOWN TO SEE WHO YOU GOT\nTHIS IS TO HIDE IT FROM THE DISCORD EMBED\n".to_string();
+    let vertical_padding = &"|\n".repeat(25);
+    let mut information = format!("{}\n", heading);
+    for (label, recipient) in recipients {
+        information += &format!(
+            "\n{}: {} ({})\n\nAddress:\n{}\n\nTheir interests are:\n{}\n",
+            label,
+            recipient.name,
+            recipient.discord_handle,
+            recipient.mailing_info,
+            recipient.interests
+        );
     }
+    let closing = &"\n\n\nRemember to check the Google Form for information about suggested price range and gift 'due date'! Happy gifting!".to_string();
 
-    let assignments = match arguments.matching_method {
-        MatchingMethod::Permutation => {
-            eprintln!("Generating valid permutation...");
-            generate_valid_permutation(configuration, arguments.do_be_verbose).assignments
+    let file_path = format!("{}/{}.txt", output_directory, sender.file_name());
+    write_file(
+        &file_path,
+        padding_disclaimer + vertical_padding + &information + closing,
+    )?;
+    Ok(file_path)
+}
+
+/// Picks the seed from the argument, the participants' random strings or at random, and says which.
+fn choose_seed(
+    seed: Option<&str>,
+    submissions: &[FormSubmission],
+) -> (Seed, Option<SeedDerivation>) {
+    let seed_derivation = seed_derivation_from_submissions(submissions);
+    match seed {
+        Some(seed) => (
+            seed::parse_seed(seed).unwrap_or_else(|message| exit_with_error(message)),
+            None,
+        ),
+        None if seed_derivation.has_contributions() => {
+            eprintln!("Derived seed from the participants' random strings.");
+            (seed_derivation.seed, Some(seed_derivation))
         }
-        MatchingMethod::FlowNetwork => {
-            eprintln!("Generating assignments via flow network...");
-            match try_generate_assignments_via_flow_network(configuration, arguments.do_be_verbose)
-            {
-                Ok(assignments) => assignments,
-                Err(message) => {
-                    eprintln!("{}", message);
-                    eprintln!("Exiting...");
-                    std::process::exit(1);
-                }
-            }
+        None => {
+            eprintln!("No participant contributed a random string, using a random seed.");
+            (seed::random_seed(), None)
         }
-    };
+    }
+}
+
+/// Writes the flow network of allowed pairs and, given a permutation, the flow of that draw. Returns the paths that were written.
+fn write_graph_files(
+    configuration: &Configuration,
+    permutation: Option<&Permutation<Rc<Participant>>>,
+    graph_formats: &[GraphFormat],
+    anonymization_salt: Option<&str>,
+    output_directory: &str,
+) -> Result<Vec<String>, String> {
+    let flow_network = flow_network::construct_flow_network(
+        &configuration.participants,
+        &configuration.cannot_send_to,
+        &configuration.cannot_receive_from,
+    );
+    let mut written_files = graph_export::write_graph(
+        flow_network.graph(),
+        output_directory,
+        "eligibility_graph",
+        graph_formats,
+        anonymization_salt,
+    )?;
+    if let Some(permutation) = permutation {
+        written_files.extend(graph_export::write_graph(
+            &flow_network.flow_for(&permutation.assignments),
+            output_directory,
+            "drawn_flow",
+            graph_formats,
+            anonymization_salt,
+        )?);
+    }
+    Ok(written_files)
+}
+
+/// The salt for anonymous IDs in graph files: the workspace's, so that IDs stay the same across its exports,
+/// or a new one for a single export.
+fn anonymization_salt(workspace: Option<&mut Workspace>) -> String {
+    match workspace {
+        Some(workspace) => workspace.anonymization_salt(),
+        None => graph_export::random_salt(),
+    }
+}
 
-    eprintln!("Writing matching files...");
-    let output_directory = write_matching_files(assignments, &arguments.output_directory_path);
-    eprintln!("Done! Wrote matchings to {}.", output_directory);
+fn exit_with_error(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn open_workspace(directory: &str) -> Workspace {
+    Workspace::open(Path::new(directory)).unwrap_or_else(|message| exit_with_error(message))
+}
 
-    let duration = start_time.elapsed();
-    eprintln!("Time elapsed: {:?}", duration);
+fn save_workspace(workspace: &Workspace) {
+    workspace
+        .save()
+        .unwrap_or_else(|message| exit_with_error(message));
+}
+
+/// Writes the files of the given senders (everyone if `only_senders` is `None`) and returns (sender name, file path) pairs.
+fn write_assignment_files(
+    permutation: &Permutation<Rc<Participant>>,
+    reveal: &Reveal,
+    only_senders: Option<&[Rc<Participant>]>,
+    output_directory: &str,
+) -> Result<Vec<(String, String)>, String> {
+    permutation
+        .assignments
+        .iter()
+        .filter(|assignment| {
+            only_senders.is_none_or(|senders| senders.contains(&assignment.sender))
+        })
+        .map(|assignment| {
+            Ok((
+                assignment.sender.name.clone(),
+                write_assignment_file(assignment, reveal, output_directory)?,
+            ))
+        })
+        .collect()
+}
+
+fn run_draw(arguments: DrawArgs) {
+    let start_time = std::time::Instant::now();
+    let human = matches!(arguments.output_format, ReportFormat::Human);
+    // Progress messages are only for people, the JSON report is printed once at the end
+    let progress = |message: &str| {
+        if human {
+            eprintln!("{}", message);
+        }
+    };
+
+    let mut report = RunReport::new(
+        Vec::new(),
+        arguments
+            .matching_method
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default(),
+    );
+    // Every failure ends up in the report, so that scripts always get one
+    if let Err(message) = draw(arguments, &progress, &mut report) {
+        progress(&message);
+        progress("Exiting...");
+        report.error = Some(message);
+    }
+    report.success = report.error.is_none();
+
+    report.elapsed_seconds = start_time.elapsed().as_secs_f64();
+    if human {
+        eprintln!("Time elapsed: {:?}", start_time.elapsed());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+    if !report.success {
+        std::process::exit(1);
+    }
+}
+
+/// Makes the draw of `run_draw`, filling in the report as it goes.
+fn draw(
+    arguments: DrawArgs,
+    progress: &dyn Fn(&str),
+    report: &mut RunReport,
+) -> Result<(), String> {
+    let mut workspace = arguments
+        .workspace
+        .as_deref()
+        .map(|directory| Workspace::open(Path::new(directory)))
+        .transpose()?;
+
+    progress("Loading configuration...");
+    let submissions = match &workspace {
+        Some(workspace) => workspace.active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .map_err(|error| format!("Failed to read {}: {}", arguments.input_file_path, error))?,
+    };
+    let configuration = configuration_from_submissions(&submissions);
+
+    progress("Loaded participants:");
+    for participant in configuration.participants.iter() {
+        progress(&format!("{:?}", participant.name));
+    }
+    report.participants = configuration
+        .sorted_participants()
+        .iter()
+        .map(|participant| participant.name.clone())
+        .collect();
+
+    // Only warn about who is affected here, the organizer shouldn't see anyone's pairing by accident
+    report.warnings = analysis::analyze(&configuration, 1).to_anonymous_warnings();
+    for warning in report.warnings.iter() {
+        progress(warning);
+    }
+
+    let seed_derivation = seed_derivation_from_submissions(&submissions);
+    let (seed, seed_derivation, seed_source) = match &arguments.seed {
+        Some(seed) => (seed::parse_seed(seed)?, None, SeedSource::Argument),
+        None if seed_derivation.has_contributions() => {
+            progress("Derived seed from the participants' random strings.");
+            (
+                seed_derivation.seed,
+                Some(seed_derivation),
+                SeedSource::Participants,
+            )
+        }
+        None => {
+            progress("No participant contributed a random string, using a random seed.");
+            (seed::random_seed(), None, SeedSource::Random)
+        }
+    };
+    report.seed = Some(hex::encode(seed));
+    report.seed_source = Some(seed_source);
+    let mut rng = seed::rng_from_seed(seed);
+
+    progress(match arguments.matching_method {
+        MatchingMethod::Permutation => "Generating valid permutation...",
+        MatchingMethod::FlowNetwork => "Generating assignments via flow network...",
+        MatchingMethod::Uniform => "Sampling a uniformly random valid assignment...",
+    });
+    let permutation = generate_permutation(
+        &arguments.matching_method,
+        &configuration,
+        arguments.do_be_verbose,
+        &PermutationBudget {
+            max_attempts: arguments.max_attempts,
+            time_limit: std::time::Duration::from_secs(arguments.time_limit),
+        },
+        &mut rng,
+    )?;
+
+    let reveal = Reveal::new(
+        &permutation,
+        seed,
+        Recipe::Draw {
+            method: report.method.clone(),
+        },
+    );
+
+    progress("Writing matching files...");
+    let output_directory = create_output_directory(
+        &arguments
+            .output_directory_path
+            .or_else(|| workspace.as_ref().map(Workspace::matchings_directory))
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    write_draw_files(&reveal, seed_derivation.as_ref(), &output_directory)?;
+    let written_files = write_assignment_files(&permutation, &reveal, None, &output_directory)?;
+    report.written_files = written_files
+        .iter()
+        .map(|(_, file_path)| file_path.clone())
+        .collect();
+    report.written_files.sort();
+    if !arguments.graph_formats.is_empty() {
+        let anonymization_salt = arguments
+            .anonymize_graphs
+            .then(|| anonymization_salt(workspace.as_mut()));
+        report.written_files.extend(write_graph_files(
+            &configuration,
+            Some(&permutation),
+            &arguments.graph_formats,
+            anonymization_salt.as_deref(),
+            &output_directory,
+        )?);
+    }
+    progress(&format!("Done! Wrote matchings to {}.", output_directory));
+    let statistics = DrawStatistics::new(&permutation, &configuration);
+    progress(statistics.to_human().trim_end());
+
+    if let Some(workspace) = workspace.as_mut() {
+        let draw_id = workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            Some(report.method.clone()),
+            reveal.clone(),
+            seed_derivation.as_ref().map(SeedDerivation::describe),
+        );
+        for (sender, file_path) in written_files.iter() {
+            workspace.mark_written(draw_id, sender, file_path);
+        }
+        workspace.log(format!(
+            "Draw #{} with {} participants, files written to {}",
+            draw_id,
+            permutation.assignments.len(),
+            output_directory
+        ));
+        workspace.save()?;
+        progress(&format!(
+            "Stored the draw in the workspace as draw #{}.",
+            draw_id
+        ));
+        report.draw_id = Some(draw_id);
+    }
+
+    progress(&format!(
+        "Publish this commitment before sending out the files: {}",
+        reveal.commitment()
+    ));
+    if seed_derivation.is_some() {
+        progress(&format!(
+            "Publish {}/seed_derivation.txt along with it, so everyone can check that their random string went into the seed.",
+            output_directory
+        ));
+    }
+    progress(&format!(
+        "Keep {}/reveal.json private until the exchange is over, then publish it so anyone can run `secret_santa verify`.",
+        output_directory
+    ));
+    progress(&format!(
+        "Seed: {} (rerun this exact draw with --seed and the same input file and matching method).",
+        reveal.seed
+    ));
+
+    report.commitment = Some(reveal.commitment());
+    report.output_directory = Some(output_directory);
+    report.statistics = Some(statistics);
+    if arguments.include_pairings {
+        report.set_pairings(&reveal);
+    }
+    Ok(())
+}
+
+fn run_init(arguments: InitArgs) {
+    let submissions =
+        read_submissions(&arguments.input_file_path).unwrap_or_else(|error| exit_with_error(error));
+    let workspace = Workspace::init(Path::new(&arguments.workspace), arguments.name, submissions)
+        .unwrap_or_else(|message| exit_with_error(message));
+    eprintln!(
+        "Created the event workspace {} with {} sign-ups.",
+        arguments.workspace,
+        workspace.state.submissions.len()
+    );
+}
+
+fn run_import(arguments: ImportArgs) {
+    let mut workspace = open_workspace(&arguments.workspace);
+    let submissions =
+        read_submissions(&arguments.input_file_path).unwrap_or_else(|error| exit_with_error(error));
+    let added = workspace.import_submissions(submissions);
+    if !added.is_empty() {
+        workspace.log(format!("Imported sign-ups from {}", added.join(", ")));
+        save_workspace(&workspace);
+    }
+    eprintln!("Added {} new sign-up(s):", added.len());
+    for name in added.iter() {
+        eprintln!("{:?}", name);
+    }
+}
+
+fn run_serve(arguments: ServeArgs) {
+    if let Some(deadline) = arguments.deadline {
+        let deadline =
+            server::parse_deadline(&deadline).unwrap_or_else(|message| exit_with_error(message));
+        let mut workspace = open_workspace(&arguments.workspace);
+        workspace.log(format!("Set the sign-up deadline to {}", deadline));
+        workspace.state.config.sign_up_deadline = Some(deadline);
+        save_workspace(&workspace);
+    }
+    server::serve(Path::new(&arguments.workspace), &arguments.address)
+        .unwrap_or_else(|message| exit_with_error(message));
+}
+
+fn run_portal_links(arguments: PortalLinksArgs) {
+    let mut workspace = open_workspace(&arguments.workspace);
+    let mut senders: Vec<String> = workspace
+        .draw(None)
+        .unwrap_or_else(|message| exit_with_error(message))
+        .participant_names()
+        .into_iter()
+        .collect();
+    senders.sort();
+
+    let issued_before = workspace.state.portal_tokens.len();
+    for sender in senders.iter() {
+        let token = workspace.portal_token(sender);
+        println!(
+            "{}\t{}",
+            sender,
+            portal::portal_link(&arguments.base_url, &token)
+        );
+    }
+    let issued = workspace.state.portal_tokens.len() - issued_before;
+    if issued > 0 {
+        workspace.log(format!("Issued {} portal link(s)", issued));
+        save_workspace(&workspace);
+    }
+    eprintln!(
+        "Send everyone their own link privately, anyone with a link can see that participant's recipient."
+    );
+}
+
+fn run_portal(arguments: PortalArgs) {
+    portal::serve(Path::new(&arguments.workspace), &arguments.address)
+        .unwrap_or_else(|message| exit_with_error(message));
+}
+
+fn run_message(arguments: MessageArgs) {
+    let mut workspace = open_workspace(&arguments.workspace);
+    mailbox::post(
+        &mut workspace,
+        &arguments.from,
+        arguments.to,
+        &arguments.text,
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+    save_workspace(&workspace);
+    eprintln!("Sent the message to {}.", arguments.to.noun());
+}
+
+fn run_inbox(arguments: InboxArgs) {
+    let workspace = open_workspace(&arguments.workspace);
+    let draw = workspace
+        .draw(None)
+        .unwrap_or_else(|message| exit_with_error(message));
+    let threads = mailbox::threads(draw, &workspace.state.messages, &arguments.name)
+        .unwrap_or_else(|message| exit_with_error(message));
+    for thread in threads {
+        println!("{}", thread.to_human());
+    }
+}
+
+fn run_api(arguments: ApiArgs) {
+    api::serve(&arguments.address).unwrap_or_else(|message| exit_with_error(message));
+}
+
+fn run_edit(arguments: WorkspaceArgs) {
+    editor::edit(open_workspace(&arguments.workspace))
+        .unwrap_or_else(|message| exit_with_error(message));
+}
+
+fn run_multi_gift(arguments: MultiGiftArgs) {
+    let workspace = arguments.workspace.as_deref().map(open_workspace);
+    let submissions = match &workspace {
+        Some(workspace) => workspace.active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+    for (name, _) in arguments
+        .send_capacity
+        .iter()
+        .chain(arguments.receive_capacity.iter())
+    {
+        if !configuration
+            .participants
+            .iter()
+            .any(|participant| &participant.name == name)
+        {
+            exit_with_error(format!("{:?} didn't sign up", name));
+        }
+    }
+    let capacity = |overrides: &[(String, usize)], participant: &Participant| {
+        overrides
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == participant.name)
+            .map_or(arguments.gifts_per_person, |(_, count)| *count)
+    };
+
+    let (seed, seed_derivation) = choose_seed(arguments.seed.as_deref(), &submissions);
+    eprintln!(
+        "Drawing {} gifts per person via flow network...",
+        arguments.gifts_per_person
+    );
+    let assignments = flow_network::try_generate_multi_assignments(
+        &configuration,
+        |participant| capacity(&arguments.send_capacity, participant),
+        |participant| capacity(&arguments.receive_capacity, participant),
+        &mut seed::rng_from_seed(seed),
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+
+    let output_directory = create_output_directory(
+        &arguments
+            .output_directory_path
+            .or_else(|| workspace.as_ref().map(Workspace::matchings_directory))
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    for sender in configuration.sorted_participants() {
+        let mut recipients: Vec<Rc<Participant>> = assignments
+            .iter()
+            .filter(|assignment| assignment.sender == sender)
+            .map(|assignment| assignment.recipient.clone())
+            .collect();
+        recipients.sort_by(|a, b| a.name.cmp(&b.name));
+        let labeled: Vec<(String, Rc<Participant>)> = recipients
+            .into_iter()
+            .enumerate()
+            .map(|(index, recipient)| (format!("Recipient {}", index + 1), recipient))
+            .collect();
+        write_gift_list_file(
+            &sender,
+            &format!(
+                "You are the Secret Santa for {} people this year!",
+                labeled.len()
+            ),
+            &labeled,
+            &output_directory,
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    if let Some(seed_derivation) = seed_derivation {
+        write_file(
+            &format!("{}/seed_derivation.txt", output_directory),
+            seed_derivation.describe(),
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    eprintln!(
+        "Done! Wrote {} gifts to {}.",
+        assignments.len(),
+        output_directory
+    );
+    eprintln!(
+        "Seed: {} (rerun this exact draw with --seed and the same input file and capacities).",
+        hex::encode(seed)
+    );
+}
+
+fn run_rounds(arguments: RoundsArgs) {
+    if arguments.rounds == 0 {
+        exit_with_error("Draw at least one round");
+    }
+    let mut workspace = arguments.workspace.as_deref().map(open_workspace);
+    let submissions = match &workspace {
+        Some(workspace) => workspace.active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+
+    let (seed, seed_derivation) = choose_seed(arguments.seed.as_deref(), &submissions);
+    eprintln!("Drawing {} rounds via flow network...", arguments.rounds);
+    let drawn = rounds::draw_rounds(
+        &configuration,
+        arguments.rounds,
+        &rounds::RoundConstraints {
+            min_cycle_length: arguments.min_cycle_length,
+            no_mutual: arguments.no_mutual,
+        },
+        &mut seed::rng_from_seed(seed),
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+    let seed_description = seed_derivation.as_ref().map(SeedDerivation::describe);
+
+    let output_directory = create_output_directory(
+        &arguments
+            .output_directory_path
+            .or_else(|| workspace.as_ref().map(Workspace::matchings_directory))
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    // Every round gets its own files and commitment, like a regular draw. The recipe names the round, so that
+    // every commitment is bound to its own round.
+    let mut commitments = Vec::new();
+    for (index, permutation) in drawn.iter().enumerate() {
+        let round_directory = format!("{}/round_{}", output_directory, index + 1);
+        fs::create_dir_all(&round_directory).unwrap_or_else(|error| {
+            exit_with_error(format!("Failed to create {}: {}", round_directory, error))
+        });
+        let reveal = Reveal::new(
+            permutation,
+            seed,
+            Recipe::Round {
+                round: index + 1,
+                rounds: drawn.len(),
+                min_cycle_length: arguments.min_cycle_length,
+                no_mutual: arguments.no_mutual,
+            },
+        );
+        commitments.push(reveal.commitment());
+        write_draw_files(&reveal, None, &round_directory)
+            .unwrap_or_else(|message| exit_with_error(message));
+        let written_files = write_assignment_files(permutation, &reveal, None, &round_directory)
+            .unwrap_or_else(|message| exit_with_error(message));
+
+        if let Some(workspace) = workspace.as_mut() {
+            let draw_id = workspace.record_draw(
+                DrawKind::Round,
+                None,
+                Some("flow-network".to_string()),
+                reveal,
+                seed_description.clone(),
+            );
+            for (sender, file_path) in written_files.iter() {
+                workspace.mark_written(draw_id, sender, file_path);
+            }
+            workspace.log(format!(
+                "Draw #{} is round {} of {}, files written to {}",
+                draw_id,
+                index + 1,
+                drawn.len(),
+                round_directory
+            ));
+        }
+    }
+
+    // And every sender gets their whole schedule in one file
+    let schedule_directory = format!("{}/schedule", output_directory);
+    fs::create_dir_all(&schedule_directory).unwrap_or_else(|error| {
+        exit_with_error(format!(
+            "Failed to create {}: {}",
+            schedule_directory, error
+        ))
+    });
+    for sender in configuration.sorted_participants() {
+        let recipients: Vec<(String, Rc<Participant>)> = drawn
+            .iter()
+            .enumerate()
+            .map(|(index, permutation)| {
+                let recipient = permutation
+                    .assignments
+                    .iter()
+                    .find(|assignment| assignment.sender == sender)
+                    .map(|assignment| assignment.recipient.clone())
+                    .unwrap();
+                (format!("Round {}", index + 1), recipient)
+            })
+            .collect();
+        write_gift_list_file(
+            &sender,
+            &format!(
+                "You are a Secret Santa in {} rounds this year, for someone else every round!",
+                drawn.len()
+            ),
+            &recipients,
+            &schedule_directory,
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    if let Some(seed_description) = seed_description {
+        write_file(
+            &format!("{}/seed_derivation.txt", output_directory),
+            seed_description,
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    eprintln!(
+        "Done! Wrote {} rounds and everyone's schedule to {}.",
+        drawn.len(),
+        output_directory
+    );
+    if let Some(workspace) = workspace.as_ref() {
+        save_workspace(workspace);
+        eprintln!(
+            "Stored the rounds in the workspace as draws #{} to #{}.",
+            workspace.state.draws.len() + 1 - drawn.len(),
+            workspace.state.draws.len()
+        );
+    }
+    eprintln!("Publish the commitments of all rounds before sending out any files:");
+    for (index, commitment) in commitments.iter().enumerate() {
+        eprintln!("Round {}: {}", index + 1, commitment);
+    }
+    eprintln!("Every round's reveal.json contains the seed that reproduces all rounds, so only publish them once the last round is over.");
+    eprintln!(
+        "Seed: {} (rerun these exact rounds with --seed and the same input file and options).",
+        hex::encode(seed)
+    );
+}
+
+fn run_swaps(arguments: SwapsArgs) {
+    let workspace = arguments.workspace.as_deref().map(open_workspace);
+    let submissions = match &workspace {
+        Some(workspace) => workspace.active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+
+    let (seed, seed_derivation) = choose_seed(arguments.seed.as_deref(), &submissions);
+    eprintln!("Pairing everyone up via blossom matching...");
+    let permutation = swaps::draw_swaps(&configuration, &mut seed::rng_from_seed(seed))
+        .unwrap_or_else(|message| exit_with_error(message));
+
+    let output_directory = create_output_directory(
+        &arguments
+            .output_directory_path
+            .or_else(|| workspace.as_ref().map(Workspace::matchings_directory))
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    let cycles = permutation.cycles();
+    for cycle in cycles.iter() {
+        for (index, sender) in cycle.iter().enumerate() {
+            let recipient = &cycle[(index + 1) % cycle.len()];
+            let heading = if cycle.len() == 2 {
+                format!("You and {} are swapping gifts this year!", recipient.name)
+            } else {
+                format!(
+                    "You are in a group of three this year: you send to {} and {} sends to you.",
+                    recipient.name,
+                    cycle[(index + cycle.len() - 1) % cycle.len()].name
+                )
+            };
+            write_gift_list_file(
+                sender,
+                &heading,
+                &[("You send to".to_string(), recipient.clone())],
+                &output_directory,
+            )
+            .unwrap_or_else(|message| exit_with_error(message));
+        }
+    }
+    if let Some(seed_derivation) = seed_derivation {
+        write_file(
+            &format!("{}/seed_derivation.txt", output_directory),
+            seed_derivation.describe(),
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    eprintln!(
+        "Done! Wrote {} pairs{} to {}.",
+        cycles.iter().filter(|cycle| cycle.len() == 2).count(),
+        if cycles.iter().any(|cycle| cycle.len() == 3) {
+            " and one group of three"
+        } else {
+            ""
+        },
+        output_directory
+    );
+    eprintln!(
+        "Seed: {} (rerun these exact swaps with --seed and the same input file).",
+        hex::encode(seed)
+    );
+}
+
+fn run_teams(arguments: TeamsArgs) {
+    let workspace = arguments.workspace.as_deref().map(open_workspace);
+    let submissions = match &workspace {
+        Some(workspace) => workspace.active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+
+    let (seed, seed_derivation) = choose_seed(arguments.seed.as_deref(), &submissions);
+    eprintln!("Drawing santa teams of {}...", arguments.team_size);
+    let drawn = teams::draw_teams(
+        &configuration,
+        arguments.team_size as usize,
+        &mut seed::rng_from_seed(seed),
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+
+    let output_directory = create_output_directory(
+        &arguments
+            .output_directory_path
+            .or_else(|| workspace.as_ref().map(Workspace::matchings_directory))
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    let describe = |participant: &Participant| {
+        format!("{} ({})", participant.name, participant.discord_handle)
+    };
+    let mut roster = String::new();
+    for (index, team) in drawn.iter().enumerate() {
+        roster += &format!(
+            "Team {}: {}\n",
+            index + 1,
+            team.members
+                .iter()
+                .map(|member| describe(member))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        for member in team.members.iter() {
+            let teammates: Vec<String> = team
+                .members
+                .iter()
+                .filter(|teammate| *teammate != member)
+                .map(|teammate| describe(teammate))
+                .collect();
+            write_gift_list_file(
+                member,
+                &format!(
+                    "You are on santa team {} with {}! Get in touch to pick a gift together.",
+                    index + 1,
+                    teammates.join(" and ")
+                ),
+                &[("Your team's recipient".to_string(), team.recipient.clone())],
+                &output_directory,
+            )
+            .unwrap_or_else(|message| exit_with_error(message));
+        }
+    }
+    // Only who is on which team, so that it can be shared without giving away any recipient
+    write_file(&format!("{}/teams.txt", output_directory), roster)
+        .unwrap_or_else(|message| exit_with_error(message));
+    if let Some(seed_derivation) = seed_derivation {
+        write_file(
+            &format!("{}/seed_derivation.txt", output_directory),
+            seed_derivation.describe(),
+        )
+        .unwrap_or_else(|message| exit_with_error(message));
+    }
+    eprintln!(
+        "Done! Wrote {} teams to {}. teams.txt lists the teams without their recipients, to share with everyone.",
+        drawn.len(),
+        output_directory
+    );
+    // There are fewer teams than people, so some participants don't get a gift and should know not to expect one
+    let without_gift: Vec<String> = configuration
+        .sorted_participants()
+        .into_iter()
+        .filter(|participant| !drawn.iter().any(|team| &team.recipient == participant))
+        .map(|participant| describe(&participant))
+        .collect();
+    eprintln!(
+        "{} participants receive no gift: {}",
+        without_gift.len(),
+        without_gift.join(", ")
+    );
+    eprintln!(
+        "Seed: {} (rerun this exact draw with --seed and the same input file and team size).",
+        hex::encode(seed)
+    );
+}
+
+fn run_validate(arguments: ValidateArgs) {
+    let submissions = match &arguments.workspace {
+        Some(directory) => open_workspace(directory).active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+
+    let report = validation::validate(&submissions, arguments.few_eligible_threshold);
+    match arguments.format {
+        ReportFormat::Human => print!("{}", report.to_human()),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+}
+
+fn run_analyze(arguments: AnalyzeArgs) {
+    let submissions = match &arguments.workspace {
+        Some(directory) => open_workspace(directory).active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+
+    let report = analysis::analyze(
+        &configuration_from_submissions(&submissions),
+        arguments.low_entropy_threshold,
+    );
+    match arguments.format {
+        ReportFormat::Human => print!("{}", report.to_human()),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+
+    if !report.feasible {
+        std::process::exit(1);
+    }
+}
+
+fn run_stats(arguments: StatsArgs) {
+    let submissions = match &arguments.workspace {
+        Some(directory) => open_workspace(directory).active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+
+    let report = counting::CountReport::new(
+        &configuration,
+        counting::count_assignments(&configuration, arguments.samples, &mut rand::thread_rng()),
+    );
+    match arguments.format {
+        ReportFormat::Human => print!("{}", report.to_human()),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+}
+
+fn run_export_graph(arguments: ExportGraphArgs) {
+    let mut workspace = arguments.workspace.as_deref().map(open_workspace);
+    let (configuration, permutation) = match &workspace {
+        Some(workspace) => match arguments.draw {
+            Some(id) => {
+                let draw = workspace
+                    .draw(Some(id))
+                    .unwrap_or_else(|message| exit_with_error(message));
+                let configuration = workspace.configuration_for_draw(draw);
+                let permutation =
+                    draw.reveal
+                        .to_permutation(&configuration)
+                        .unwrap_or_else(|message| {
+                            exit_with_error(format!("The stored draw is not valid: {}", message))
+                        });
+                (configuration, Some(permutation))
+            }
+            None => (
+                configuration_from_submissions(&workspace.active_submissions()),
+                None,
+            ),
+        },
+        None => (
+            read_configuration_from_csv(&arguments.input_file_path),
+            None,
+        ),
+    };
+
+    let anonymization_salt = arguments.anonymize.then(|| {
+        let creates_salt = workspace
+            .as_ref()
+            .is_some_and(|workspace| workspace.state.anonymization_salt.is_none());
+        let salt = anonymization_salt(workspace.as_mut());
+        if creates_salt {
+            save_workspace(workspace.as_ref().unwrap());
+        }
+        salt
+    });
+    let written_files = write_graph_files(
+        &configuration,
+        permutation.as_ref(),
+        &arguments.graph_formats,
+        anonymization_salt.as_deref(),
+        &arguments.output_directory_path,
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+    for file_path in written_files.iter() {
+        eprintln!("Wrote {}", file_path);
+    }
+}
+
+fn run_bias_report(arguments: BiasReportArgs) {
+    let submissions = match &arguments.workspace {
+        Some(directory) => open_workspace(directory).active_submissions(),
+        None => read_submissions(&arguments.input_file_path)
+            .unwrap_or_else(|error| exit_with_error(error)),
+    };
+    let configuration = configuration_from_submissions(&submissions);
+    let expected = bias::uniform_pair_probabilities(&configuration)
+        .unwrap_or_else(|message| exit_with_error(message));
+
+    let matching_methods = if arguments.matching_methods.is_empty() {
+        MatchingMethod::value_variants().to_vec()
+    } else {
+        arguments.matching_methods
+    };
+    let mut rng = rand::thread_rng();
+    let biases: Vec<bias::MethodBias> = matching_methods
+        .iter()
+        .map(|matching_method| {
+            let name = matching_method
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default();
+            eprintln!("Running {} {} times...", name, arguments.runs);
+            bias::measure_bias(name, &configuration, &expected, arguments.runs, || {
+                generate_permutation(
+                    matching_method,
+                    &configuration,
+                    false,
+                    &PermutationBudget::default(),
+                    &mut rng,
+                )
+            })
+        })
+        .collect();
+
+    match arguments.format {
+        ReportFormat::Human => {
+            for method_bias in biases.iter() {
+                print!("{}", method_bias.to_human(arguments.shown_pairs));
+            }
+        }
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&biases).unwrap()),
+    }
+}
+
+fn run_status(arguments: WorkspaceArgs) {
+    let workspace = open_workspace(&arguments.workspace);
+    let state = &workspace.state;
+
+    println!("{}", state.config.name);
+    if let Some(deadline) = &state.config.sign_up_deadline {
+        println!("Sign-ups close at {}", deadline);
+    }
+    println!(
+        "{} sign-ups, {} withdrawn",
+        state.submissions.len(),
+        state.withdrawn.len()
+    );
+    for name in state.withdrawn.iter() {
+        println!("  withdrawn: {}", name);
+    }
+
+    println!();
+    println!("Draws:");
+    for draw in state.draws.iter() {
+        let pending: Vec<&String> = draw
+            .deliveries
+            .iter()
+            .filter(|(_, status)| **status == DeliveryStatus::Pending)
+            .map(|(sender, _)| sender)
+            .collect();
+        println!(
+            "  #{} {:?}{} at {}, {} participants, commitment {}",
+            draw.id,
+            draw.kind,
+            draw.parent
+                .map(|parent| format!(" of #{}", parent))
+                .unwrap_or_default(),
+            draw.created_at,
+            draw.deliveries.len(),
+            draw.commitment
+        );
+        // Portal links are issued for the latest draw
+        if !workspace.state.portal_tokens.is_empty()
+            && state.draws.last().map(|latest| latest.id) == Some(draw.id)
+        {
+            let not_viewed: Vec<&str> = draw
+                .deliveries
+                .keys()
+                .filter(|sender| !draw.portal_views.contains_key(*sender))
+                .map(|sender| sender.as_str())
+                .collect();
+            if !not_viewed.is_empty() {
+                println!(
+                    "    not viewed in the portal yet: {}",
+                    not_viewed.join(", ")
+                );
+            }
+        }
+        if !pending.is_empty() {
+            println!(
+                "    files not written yet for: {}",
+                pending
+                    .iter()
+                    .map(|sender| sender.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    println!();
+    println!("History:");
+    for entry in state.history.iter() {
+        println!("  {} {}", entry.at, entry.message);
+    }
+}
+
+fn run_reveal(arguments: RevealArgs) {
+    let mut workspace = open_workspace(&arguments.workspace);
+    let draw = workspace
+        .draw(arguments.draw)
+        .unwrap_or_else(|message| exit_with_error(message))
+        .clone();
+
+    for assignment in draw.reveal.assignments.iter() {
+        println!("{} -> {}", assignment.sender, assignment.recipient);
+    }
+
+    workspace.log(format!("Revealed draw #{}", draw.id));
+    save_workspace(&workspace);
+}
+
+fn run_visualize(arguments: VisualizeArgs) {
+    let (reveal, title, mut workspace) = match &arguments.reveal_file_path {
+        Some(reveal_file_path) => (
+            read_reveal(reveal_file_path),
+            "Secret Santa".to_string(),
+            None,
+        ),
+        None => {
+            let workspace = open_workspace(arguments.workspace.as_deref().unwrap());
+            let draw = workspace
+                .draw(arguments.draw)
+                .unwrap_or_else(|message| exit_with_error(message));
+            (
+                draw.reveal.clone(),
+                workspace.state.config.name.clone(),
+                Some(workspace),
+            )
+        }
+    };
+
+    let names: HashSet<String> = reveal
+        .assignments
+        .iter()
+        .map(|assignment| assignment.sender.clone())
+        .collect();
+    let assignments = reveal
+        .assignments
+        .iter()
+        .map(|assignment| Assignment {
+            sender: assignment.sender.clone(),
+            recipient: assignment.recipient.clone(),
+        })
+        .collect();
+    let permutation = Permutation::try_new(assignments, &names)
+        .unwrap_or_else(|message| exit_with_error(message));
+    let cycles = visualization::arrange_cycles(permutation.cycles());
+
+    let options = RingOptions {
+        hide_surnames: arguments.hide_surnames,
+        highlight_longest: arguments.highlight_longest,
+    };
+    let contents = match arguments.format {
+        VisualizationFormat::Svg => visualization::render_svg(&cycles, &options),
+        VisualizationFormat::Html => visualization::render_html(&cycles, &options, &title),
+    };
+    let output_file_path = arguments
+        .output_file_path
+        .unwrap_or_else(|| format!("./gift_graph.{}", arguments.format.extension()));
+    fs::write(&output_file_path, contents).unwrap_or_else(|error| {
+        exit_with_error(format!("Failed to write {}: {}", output_file_path, error))
+    });
+    eprintln!("Wrote {}", output_file_path);
+
+    if let Some(workspace) = workspace.as_mut() {
+        let draw_id = workspace.draw(arguments.draw).unwrap().id;
+        workspace.log(format!(
+            "Visualized draw #{} to {}",
+            draw_id, output_file_path
+        ));
+        save_workspace(workspace);
+    }
+}
+
+fn run_reissue(arguments: ReissueArgs) {
+    let mut workspace = open_workspace(&arguments.workspace);
+    let output_directory = arguments
+        .output_directory_path
+        .unwrap_or_else(|| workspace.matchings_directory());
+    let file_path = reissue::reissue(&mut workspace, arguments.draw, &arguments.name, || {
+        create_output_directory(&output_directory)
+    })
+    .unwrap_or_else(|message| exit_with_error(message));
+    save_workspace(&workspace);
+
+    // Deliberately only print where the file is, so that the pairing doesn't show up in the organizer's terminal
+    eprintln!("Wrote {}.", file_path);
+}
+
+fn run_verify(arguments: VerifyArgs) {
+    let (configuration, commitment, reveal, seed_derivation, parent) = match &arguments.workspace {
+        Some(directory) => {
+            let workspace = open_workspace(directory);
+            let draw = workspace
+                .draw(arguments.draw)
+                .unwrap_or_else(|message| exit_with_error(message));
+            let parent = draw.parent.map(|id| {
+                let parent = workspace
+                    .draw(Some(id))
+                    .unwrap_or_else(|message| exit_with_error(message));
+                (
+                    parent.reveal.clone(),
+                    workspace.configuration_for_draw(parent),
+                )
+            });
+            (
+                workspace.configuration_for_draw(draw),
+                arguments
+                    .commitment
+                    .unwrap_or_else(|| draw.commitment.clone()),
+                draw.reveal.clone(),
+                workspace.seed_derivation_for_draw(draw),
+                parent,
+            )
+        }
+        None => {
+            let configuration = read_configuration_from_csv(&arguments.input_file_path);
+            let reveal = read_reveal(&arguments.reveal_file_path.unwrap_or_else(|| {
+                exit_with_error("--reveal-file-path is required without --workspace")
+            }));
+            let parent_reveal = arguments
+                .parent_reveal_file_path
+                .as_deref()
+                .map(read_reveal);
+            // The input file is the one the previous draw was made from, which still has the participants who dropped out
+            let (configuration, parent_configuration, seed_derivation) = match &reveal.recipe {
+                Recipe::Repair { removed, .. } => (
+                    configuration
+                        .without_participants(&removed.iter().cloned().collect::<HashSet<_>>()),
+                    Some(configuration),
+                    None,
+                ),
+                // The input file has the late joiners, who weren't part of the previous draw
+                Recipe::Insert { added, .. } => {
+                    let previous =
+                        configuration.without_participants(&added.iter().cloned().collect());
+                    (configuration, Some(previous), None)
+                }
+                _ => (
+                    configuration,
+                    None,
+                    Some(read_seed_derivation_from_csv(&arguments.input_file_path)),
+                ),
+            };
+            (
+                configuration,
+                arguments.commitment.unwrap_or_else(|| {
+                    exit_with_error("--commitment is required without --workspace")
+                }),
+                reveal,
+                seed_derivation,
+                parent_reveal.zip(parent_configuration),
+            )
+        }
+    };
+
+    if let Some(seed_derivation) =
+        seed_derivation.filter(|seed_derivation| seed_derivation.has_contributions())
+    {
+        seed_derivation
+            .ensure_derived_seed(&reveal.seed)
+            .unwrap_or_else(|message| {
+                exit_with_error(format!("Verification failed: {}.", message))
+            });
+        eprintln!("The revealed seed is the one derived from the participants' random strings.");
+    }
+
+    match commitment::verify_reveal(&commitment, &reveal, &configuration) {
+        Ok(permutation) => {
+            eprintln!(
+                "The reveal matches the commitment and all {} assignments satisfy the exclusion constraints.",
+                permutation.assignments.len()
+            );
+        }
+        Err(message) => exit_with_error(format!("Verification failed: {}", message)),
+    }
+
+    if matches!(reveal.recipe, Recipe::Repair { .. } | Recipe::Insert { .. }) && parent.is_none() {
+        eprintln!("This draw was made from a previous draw. Pass that draw's reveal.json with --parent-reveal-file-path to rerun it.");
+        return;
+    }
+    let parent = parent
+        .as_ref()
+        .map(|(reveal, configuration)| replay::Parent {
+            reveal,
+            configuration,
+        });
+    replay::verify_rerun(&reveal, &configuration, parent.as_ref())
+        .unwrap_or_else(|message| exit_with_error(format!("Verification failed: {}", message)));
+    eprintln!("Rerunning the draw from the revealed seed gives exactly the revealed assignments.");
+}
+
+fn run_verify_proof(arguments: VerifyProofArgs) {
+    let result = arguments.proof.parse::<InclusionProof>().and_then(|proof| {
+        commitment::verify_inclusion(
+            &arguments.commitment,
+            &arguments.sender,
+            &arguments.recipient,
+            &proof,
+        )
+    });
+
+    match result {
+        Ok(()) => eprintln!(
+            "{} -> {} is part of the draw with commitment {}.",
+            arguments.sender, arguments.recipient, arguments.commitment
+        ),
+        Err(message) => exit_with_error(format!("Verification failed: {}", message)),
+    }
+}
+
+fn read_reveal(reveal_file_path: &str) -> Reveal {
+    fs::read_to_string(reveal_file_path)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|error| error.to_string()))
+        .unwrap_or_else(|error| {
+            exit_with_error(format!("Failed to read {}: {}", reveal_file_path, error))
+        })
+}
+
+/// Writes the files of a draw derived from a previous one, records it in the workspace if there is one, and reports who needs a new file.
+fn finish_derived_draw(
+    workspace: Option<&mut Workspace>,
+    kind: DrawKind,
+    parent: Option<usize>,
+    repaired: &repair::RepairedDraw,
+    configuration: &Configuration,
+    reveal: Reveal,
+    output_directory_path: Option<String>,
+) {
+    let output_directory = create_output_directory(
+        &output_directory_path
+            .or_else(|| {
+                workspace
+                    .as_ref()
+                    .map(|workspace| workspace.matchings_directory())
+            })
+            .unwrap_or_else(|| "./matchings".to_string()),
+    );
+    write_draw_files(&reveal, None, &output_directory)
+        .unwrap_or_else(|message| exit_with_error(message));
+    let written_files = write_assignment_files(
+        &repaired.permutation,
+        &reveal,
+        Some(&repaired.changed_senders),
+        &output_directory,
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+
+    eprintln!(
+        "Changed {} of {} assignments. Wrote new files to {} for:",
+        repaired.changed_senders.len(),
+        repaired.permutation.assignments.len(),
+        output_directory
+    );
+    for sender in repaired.changed_senders.iter() {
+        eprintln!("{:?}", sender.name);
+    }
+    eprintln!(
+        "Everyone else keeps their current file. Publish the new commitment: {}",
+        reveal.commitment()
+    );
+    eprint!(
+        "{}",
+        DrawStatistics::new(&repaired.permutation, configuration).to_human()
+    );
+
+    if let Some(workspace) = workspace {
+        let draw_id = workspace.record_draw(kind, parent, None, reveal, None);
+        for (sender, file_path) in written_files.iter() {
+            workspace.mark_written(draw_id, sender, file_path);
+        }
+        workspace.log(format!(
+            "Draw #{} ({:?} of #{}) changed the assignments of {}",
+            draw_id,
+            kind,
+            parent.unwrap_or_default(),
+            repaired
+                .changed_senders
+                .iter()
+                .map(|sender| sender.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        save_workspace(workspace);
+        eprintln!("Stored the new draw in the workspace as draw #{}.", draw_id);
+    }
+}
+
+fn run_repair(arguments: RepairArgs) {
+    let mut workspace = arguments.workspace.as_deref().map(open_workspace);
+    let (configuration, previous_reveal, parent) = match &workspace {
+        Some(workspace) => {
+            let draw = workspace
+                .draw(None)
+                .unwrap_or_else(|message| exit_with_error(message));
+            (
+                workspace.configuration_for_draw(draw),
+                draw.reveal.clone(),
+                Some(draw.id),
+            )
+        }
+        None => (
+            read_configuration_from_csv(&arguments.input_file_path),
+            read_reveal(arguments.reveal_file_path.as_deref().unwrap()),
+            None,
+        ),
+    };
+
+    let removed_names: HashSet<String> = arguments.removed_names.into_iter().collect();
+    for name in removed_names.iter() {
+        if !configuration.participants.iter().any(|p| &p.name == name) {
+            exit_with_error(format!("{:?} is not a participant in the draw", name));
+        }
+    }
+
+    let previous = previous_reveal
+        .to_permutation(&configuration)
+        .unwrap_or_else(|message| {
+            exit_with_error(format!("The previous draw is not valid: {}", message))
+        });
+    let remaining = configuration.without_participants(&removed_names);
+
+    // The seed comes from the previous draw, so that anyone can rerun the repair and the organizer can't pick one
+    let previous_commitment = previous_reveal.commitment();
+    let seed = seed::derived_seed(
+        seed::parse_seed(&previous_reveal.seed).unwrap_or_else(|message| exit_with_error(message)),
+        &previous_commitment,
+    );
+    let repaired =
+        repair::repair_without_dropouts(&previous, &remaining, &mut seed::rng_from_seed(seed))
+            .unwrap_or_else(|message| exit_with_error(message));
+    let mut removed: Vec<String> = removed_names.iter().cloned().collect();
+    removed.sort();
+    let reveal = Reveal::new(
+        &repaired.permutation,
+        seed,
+        Recipe::Repair {
+            parent: previous_commitment,
+            removed,
+        },
+    );
+
+    if let Some(workspace) = workspace.as_mut() {
+        workspace
+            .state
+            .withdrawn
+            .extend(removed_names.iter().cloned());
+        workspace.log(format!(
+            "Withdrew {}",
+            removed_names.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    finish_derived_draw(
+        workspace.as_mut(),
+        DrawKind::Repair,
+        parent,
+        &repaired,
+        &remaining,
+        reveal,
+        arguments.output_directory_path,
+    );
+}
+
+fn run_insert(arguments: InsertArgs) {
+    let mut workspace = arguments.workspace.as_deref().map(open_workspace);
+    let added_names: HashSet<String> = arguments.added_names.iter().cloned().collect();
+    let (configuration, previous_reveal, parent) = match &workspace {
+        Some(workspace) => {
+            let draw = workspace
+                .draw(None)
+                .unwrap_or_else(|message| exit_with_error(message));
+            let names: HashSet<String> = draw
+                .participant_names()
+                .union(&added_names)
+                .cloned()
+                .collect();
+            let submissions: Vec<_> = workspace
+                .active_submissions()
+                .into_iter()
+                .filter(|submission| names.contains(&submission.name))
+                .collect();
+            (
+                configuration_from_submissions(&submissions),
+                draw.reveal.clone(),
+                Some(draw.id),
+            )
+        }
+        None => (
+            read_configuration_from_csv(&arguments.input_file_path),
+            read_reveal(arguments.reveal_file_path.as_deref().unwrap()),
+            None,
+        ),
+    };
+
+    let joiners: Vec<Rc<Participant>> = arguments
+        .added_names
+        .iter()
+        .map(|name| {
+            configuration
+                .participants
+                .iter()
+                .find(|p| &p.name == name)
+                .cloned()
+                .unwrap_or_else(|| exit_with_error(format!("{:?} is not on the roster", name)))
+        })
+        .collect();
+
+    let previous = previous_reveal
+        .to_permutation(&configuration.without_participants(&added_names))
+        .unwrap_or_else(|message| {
+            exit_with_error(format!("The previous draw is not valid: {}", message))
+        });
+
+    // Like a repair, the seed comes from the previous draw
+    let previous_commitment = previous_reveal.commitment();
+    let seed = seed::derived_seed(
+        seed::parse_seed(&previous_reveal.seed).unwrap_or_else(|message| exit_with_error(message)),
+        &previous_commitment,
+    );
+    let inserted = repair::insert_late_joiners(
+        &previous,
+        &joiners,
+        &configuration,
+        &mut seed::rng_from_seed(seed),
+    )
+    .unwrap_or_else(|message| exit_with_error(message));
+    let reveal = Reveal::new(
+        &inserted.permutation,
+        seed,
+        Recipe::Insert {
+            parent: previous_commitment,
+            added: arguments.added_names.clone(),
+        },
+    );
+
+    finish_derived_draw(
+        workspace.as_mut(),
+        DrawKind::Insert,
+        parent,
+        &inserted,
+        &configuration,
+        reveal,
+        arguments.output_directory_path,
+    );
+}
+
+fn main() {
+    let arguments = Args::parse();
+
+    match arguments.command {
+        None => run_draw(arguments.draw_arguments),
+        Some(Command::Init(arguments)) => run_init(arguments),
+        Some(Command::Import(arguments)) => run_import(arguments),
+        Some(Command::Validate(arguments)) => run_validate(arguments),
+        Some(Command::Analyze(arguments)) => run_analyze(arguments),
+        Some(Command::Stats(arguments)) => run_stats(arguments),
+        Some(Command::ExportGraph(arguments)) => run_export_graph(arguments),
+        Some(Command::BiasReport(arguments)) => run_bias_report(arguments),
+        Some(Command::Status(arguments)) => run_status(arguments),
+        Some(Command::Reveal(arguments)) => run_reveal(arguments),
+        Some(Command::Visualize(arguments)) => run_visualize(arguments),
+        Some(Command::Reissue(arguments)) => run_reissue(arguments),
+        Some(Command::Verify(arguments)) => run_verify(arguments),
+        Some(Command::VerifyProof(arguments)) => run_verify_proof(arguments),
+        Some(Command::Repair(arguments)) => run_repair(arguments),
+        Some(Command::Insert(arguments)) => run_insert(arguments),
+        Some(Command::Serve(arguments)) => run_serve(arguments),
+        Some(Command::PortalLinks(arguments)) => run_portal_links(arguments),
+        Some(Command::Portal(arguments)) => run_portal(arguments),
+        Some(Command::Message(arguments)) => run_message(arguments),
+        Some(Command::Inbox(arguments)) => run_inbox(arguments),
+        Some(Command::Api(arguments)) => run_api(arguments),
+        Some(Command::Edit(arguments)) => run_edit(arguments),
+        Some(Command::MultiGift(arguments)) => run_multi_gift(arguments),
+        Some(Command::Rounds(arguments)) => run_rounds(arguments),
+        Some(Command::Swaps(arguments)) => run_swaps(arguments),
+        Some(Command::Teams(arguments)) => run_teams(arguments),
+    }
 }
@@ -0,0 +1,61 @@
+//! The matching methods a draw can use, shared by the CLI, the HTTP API and the replay of committed draws.
+
+use std::rc::Rc;
+
+use clap::ValueEnum;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    configuration::{Configuration, Participant},
+    derangement, flow_network,
+    permutation::Permutation,
+    uniform,
+};
+
+#[derive(Clone, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchingMethod {
+    Permutation,
+    FlowNetwork,
+    Uniform,
+}
+
+/// When the "permutation" method gives up
+pub struct PermutationBudget {
+    pub max_attempts: u64,
+    pub time_limit: std::time::Duration,
+}
+
+impl Default for PermutationBudget {
+    fn default() -> Self {
+        PermutationBudget {
+            max_attempts: 1_000_000,
+            time_limit: std::time::Duration::from_secs(10),
+        }
+    }
+}
+
+pub fn generate_permutation<R: Rng>(
+    matching_method: &MatchingMethod,
+    configuration: &Configuration,
+    do_be_verbose: bool,
+    permutation_budget: &PermutationBudget,
+    rng: &mut R,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    match matching_method {
+        MatchingMethod::Permutation => derangement::generate_valid_permutation(
+            configuration,
+            do_be_verbose,
+            permutation_budget,
+            rng,
+        ),
+        MatchingMethod::FlowNetwork => {
+            flow_network::try_generate_assignments(configuration, do_be_verbose, rng).and_then(
+                |assignments| Permutation::try_new(assignments, &configuration.participants),
+            )
+        }
+        MatchingMethod::Uniform => uniform::sample_uniform_assignments(configuration, rng)
+            .and_then(|assignments| Permutation::try_new(assignments, &configuration.participants)),
+    }
+}
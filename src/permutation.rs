@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -60,4 +60,62 @@ where
 
         Ok(())
     }
+
+    /// Splits the permutation into its cycles, each listed in gift-giving order (everyone sends to the next person,
+    /// the last one to the first). Neither the cycles nor their starting points are in any particular order.
+    pub fn cycles(&self) -> Vec<Vec<T>> {
+        let recipient_of: HashMap<&T, &T> = self
+            .assignments
+            .iter()
+            .map(|assignment| (&assignment.sender, &assignment.recipient))
+            .collect();
+
+        let mut visited: HashSet<&T> = HashSet::new();
+        let mut cycles = Vec::new();
+        for assignment in self.assignments.iter() {
+            let mut current = &assignment.sender;
+            if visited.contains(current) {
+                continue;
+            }
+            let mut cycle = Vec::new();
+            while visited.insert(current) {
+                cycle.push(current.clone());
+                current = recipient_of[current];
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        let assignments = [(1, 2), (2, 3), (3, 1), (4, 5), (5, 4)]
+            .into_iter()
+            .map(|(sender, recipient)| Assignment { sender, recipient })
+            .collect();
+        let permutation =
+            Permutation::try_new(assignments, &HashSet::from([1, 2, 3, 4, 5])).unwrap();
+
+        let mut cycles = permutation.cycles();
+        cycles.sort_by_key(|cycle| cycle.len());
+
+        assert_eq!(cycles.len(), 2);
+        assert_eq!(cycles[0].len(), 2);
+        assert_eq!(cycles[1].len(), 3);
+        // Every cycle follows the assignments
+        for cycle in cycles.iter() {
+            for (index, sender) in cycle.iter().enumerate() {
+                let recipient = &cycle[(index + 1) % cycle.len()];
+                assert!(permutation.assignments.contains(&Assignment {
+                    sender: *sender,
+                    recipient: *recipient
+                }));
+            }
+        }
+    }
 }
@@ -0,0 +1,354 @@
+//! The participant portal: a small HTTP server where every participant looks up their own assignment
+//! through a secret link, instead of being sent a file.
+//!
+//! Each page is rendered for exactly one token and only shows that participant's recipient, from the latest draw,
+//! along with their anonymous messages. There is deliberately no page that lists participants, tokens or pairings.
+
+use std::{fmt::Write, path::Path};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    mailbox::{self, Correspondent, MAX_MESSAGE_LENGTH},
+    server::{escape_html, html_page, html_response, read_form, HtmlResponse},
+    workspace::Workspace,
+};
+
+pub const VIEW_PATH: &str = "/view/";
+const MESSAGES_SUFFIX: &str = "/messages";
+
+/// The link a participant uses to open their page.
+pub fn portal_link(base_url: &str, token: &str) -> String {
+    format!("{}{}{}", base_url.trim_end_matches('/'), VIEW_PATH, token)
+}
+
+fn not_found() -> HtmlResponse {
+    html_response(
+        404,
+        html_page(
+            "Not found",
+            "  <p>This link isn't valid. Ask the organizer for your link.</p>\n",
+        ),
+    )
+}
+
+/// Renders the page for the participant the token was issued to and records the view.
+fn assignment_page(workspace: &mut Workspace, token: &str) -> Result<String, String> {
+    let name = workspace
+        .participant_for_token(token)
+        .ok_or_else(|| "Unknown token".to_string())?
+        .to_string();
+    let draw = workspace.draw(None)?;
+    let title = workspace.state.config.name.clone();
+
+    let Some(recipient_name) = draw.recipient_of(&name) else {
+        return Ok(html_page(
+            &title,
+            &format!(
+                "  <p>Hi {}! You aren't part of the current draw. Ask the organizer if that's a mistake.</p>\n",
+                escape_html(&name)
+            ),
+        ));
+    };
+    let recipient = workspace
+        .submissions_for_draw(draw)
+        .into_iter()
+        .find(|submission| submission.name == recipient_name)
+        .ok_or_else(|| format!("The sign-up of {:?} is missing", recipient_name))?;
+
+    let mut content = String::new();
+    writeln!(
+        content,
+        "  <p>Hi {}! You are the Secret Santa for <strong>{}</strong> ({}).</p>",
+        escape_html(&name),
+        escape_html(&recipient.name),
+        escape_html(&recipient.discord_handle)
+    )
+    .unwrap();
+    for (heading, text) in [
+        ("Address", &recipient.mailing_info),
+        ("Their interests", &recipient.interests),
+    ] {
+        writeln!(content, "  <h2>{}</h2>", heading).unwrap();
+        writeln!(
+            content,
+            "  <p style=\"white-space: pre-wrap\">{}</p>",
+            escape_html(text)
+        )
+        .unwrap();
+    }
+    writeln!(
+        content,
+        "  <p><small>Draw commitment: <code>{}</code><br>Your proof of inclusion: <code>{}</code></small></p>",
+        escape_html(&draw.commitment),
+        draw.reveal
+            .proof_for(&name)
+            .map(|proof| proof.to_string())
+            .unwrap_or_default()
+    )
+    .unwrap();
+
+    for thread in mailbox::threads(draw, &workspace.state.messages, &name)? {
+        let (heading, to) = match thread.with {
+            Correspondent::Recipient => {
+                ("Ask your recipient (they won't know it's you)", "recipient")
+            }
+            Correspondent::Santa => ("Messages from your Secret Santa", "santa"),
+        };
+        writeln!(content, "  <h2>{}</h2>", heading).unwrap();
+        for message in thread.messages.iter() {
+            writeln!(
+                content,
+                "  <p><small>{}</small> <strong>{}:</strong> <span style=\"white-space: pre-wrap\">{}</span></p>",
+                escape_html(&message.at),
+                thread.author_label(message),
+                escape_html(&message.text)
+            )
+            .unwrap();
+        }
+        writeln!(
+            content,
+            "  <form method=\"post\" action=\"{}{}{}\"><input type=\"hidden\" name=\"to\" value=\"{}\"><textarea name=\"text\" rows=\"2\" maxlength=\"{}\"></textarea><button type=\"submit\">Send</button></form>",
+            VIEW_PATH,
+            escape_html(token),
+            MESSAGES_SUFFIX,
+            to,
+            MAX_MESSAGE_LENGTH
+        )
+        .unwrap();
+    }
+
+    // Only the first view is worth a history entry, reloading the page shouldn't flood the history
+    let draw_id = draw.id;
+    if workspace.mark_viewed(draw_id, &name) {
+        workspace.log(format!(
+            "{} viewed their assignment of draw #{} in the portal",
+            name, draw_id
+        ));
+        workspace.save()?;
+    }
+    Ok(html_page(&title, &content))
+}
+
+/// Stores a message the participant sent from their page.
+fn post_message(
+    request: &mut Request,
+    workspace: &mut Workspace,
+    token: &str,
+) -> Result<(), String> {
+    let form = read_form(request)?;
+    let value = |key: &str| {
+        form.iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    };
+    let to = match value("to") {
+        "santa" => Correspondent::Santa,
+        "recipient" => Correspondent::Recipient,
+        _ => return Err("Unknown correspondent".to_string()),
+    };
+    let name = workspace
+        .participant_for_token(token)
+        .ok_or_else(|| "Unknown token".to_string())?
+        .to_string();
+    mailbox::post(workspace, &name, to, value("text"))?;
+    workspace.save()
+}
+
+fn handle_request(request: &mut Request, workspace_directory: &Path) -> HtmlResponse {
+    let path = match request.url().strip_prefix(VIEW_PATH) {
+        Some(path) => path.to_string(),
+        None => return not_found(),
+    };
+    let (token, posting) = match (request.method(), path.strip_suffix(MESSAGES_SUFFIX)) {
+        (Method::Post, Some(token)) => (token, true),
+        (Method::Get, None) => (path.as_str(), false),
+        _ => return not_found(),
+    };
+    if token.is_empty() {
+        return not_found();
+    }
+    let mut workspace = match Workspace::open(workspace_directory) {
+        Ok(workspace) => workspace,
+        Err(message) => {
+            eprintln!("{}", message);
+            return html_response(500, html_page("Error", "  <p>Something went wrong.</p>\n"));
+        }
+    };
+    if workspace.participant_for_token(token).is_none() {
+        return not_found();
+    }
+    if posting {
+        return match post_message(request, &mut workspace, token) {
+            // Go back to the page, so that reloading it doesn't send the message again
+            Ok(()) => Response::from_string(String::new())
+                .with_status_code(303)
+                .with_header(
+                    Header::from_bytes(&b"Location"[..], format!("{}{}", VIEW_PATH, token))
+                        .unwrap(),
+                ),
+            Err(message) => html_response(
+                400,
+                html_page(
+                    "Message not sent",
+                    &format!("  <p class=\"error\">{}</p>\n", escape_html(&message)),
+                ),
+            ),
+        };
+    }
+    match assignment_page(&mut workspace, token) {
+        Ok(page) => html_response(200, page)
+            // Keep the token out of caches and out of the Referer header of any link on the page
+            .with_header(Header::from_bytes(&b"Cache-Control"[..], &b"no-store"[..]).unwrap())
+            .with_header(Header::from_bytes(&b"Referrer-Policy"[..], &b"no-referrer"[..]).unwrap()),
+        Err(message) => {
+            eprintln!("{}", message);
+            html_response(500, html_page("Error", "  <p>Something went wrong.</p>\n"))
+        }
+    }
+}
+
+/// Serves the participant portal for the workspace until the process is stopped.
+pub fn serve(workspace_directory: &Path, address: &str) -> Result<(), String> {
+    let server = Server::http(address)
+        .map_err(|error| format!("Failed to listen on {}: {}", address, error))?;
+    eprintln!("Serving the participant portal on http://{}/", address);
+
+    for mut request in server.incoming_requests() {
+        let response = handle_request(&mut request, workspace_directory);
+        if let Err(error) = request.respond(response) {
+            eprintln!("Failed to respond: {}", error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use tiny_http::TestRequest;
+
+    use super::*;
+    use crate::{
+        commitment::{Recipe, Reveal},
+        configuration::test_support,
+        workspace::{test_support::workspace, DrawKind},
+    };
+
+    const NAMES: [&str; 5] = ["Alice", "Bob", "Charlie", "David", "Eve"];
+
+    /// A workspace with a draw where everyone sends to the next name, and a portal token for everyone.
+    fn portal_workspace(test: &str) -> Workspace {
+        let mut workspace = workspace(test, &NAMES);
+        let permutation = test_support::cycle(&test_support::configuration(&NAMES, &[]));
+        workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            None,
+            Reveal::new(
+                &permutation,
+                [0; 32],
+                Recipe::Draw {
+                    method: "flow-network".to_string(),
+                },
+            ),
+            None,
+        );
+        for name in NAMES {
+            workspace.portal_token(name);
+        }
+        workspace.save().unwrap();
+        workspace
+    }
+
+    fn request(workspace: &Workspace, method: Method, path: &str) -> (u16, String) {
+        let mut request = TestRequest::new()
+            .with_method(method)
+            .with_path(path)
+            .into();
+        let response = handle_request(&mut request, &workspace.directory);
+        let status = response.status_code().0;
+        let mut body = String::new();
+        response.into_reader().read_to_string(&mut body).unwrap();
+        (status, body)
+    }
+
+    #[test]
+    fn test_token_shows_only_its_own_assignment() {
+        let workspace = portal_workspace("portal_pages");
+
+        for (index, name) in NAMES.iter().enumerate() {
+            let recipient = NAMES[(index + 1) % NAMES.len()];
+            let token = &workspace.state.portal_tokens[*name];
+            let (status, body) = request(&workspace, Method::Get, &format!("/view/{}", token));
+
+            assert_eq!(status, 200);
+            assert!(body.contains(&format!(
+                "Hi {}! You are the Secret Santa for <strong>{}</strong>",
+                name, recipient
+            )));
+            // Nobody else is mentioned, so neither another pairing nor the mapping can be read off the page
+            for other in NAMES
+                .iter()
+                .filter(|other| ![*name, recipient].contains(other))
+            {
+                assert!(!body.contains(other), "{}'s page mentions {}", name, other);
+            }
+        }
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_tokens_and_routes_are_not_found() {
+        let workspace = portal_workspace("portal_not_found");
+        let token = workspace.state.portal_tokens["Alice"].clone();
+
+        for (method, path) in [
+            (Method::Get, "/".to_string()),
+            (Method::Get, "/view".to_string()),
+            (Method::Get, "/view/".to_string()),
+            (Method::Get, "/view/0123456789abcdef".to_string()),
+            (Method::Get, format!("/view/{}x", token)),
+            (Method::Get, format!("/view/{}", &token[1..])),
+            (Method::Get, format!("/view/{}/messages", token)),
+            (Method::Post, format!("/view/{}", token)),
+            (Method::Post, "/view//messages".to_string()),
+            (Method::Post, "/view/0123456789abcdef/messages".to_string()),
+            (Method::Get, "/draws".to_string()),
+        ] {
+            let (status, body) = request(&workspace, method, &path);
+            assert_eq!(status, 404, "{}", path);
+            for name in NAMES {
+                assert!(!body.contains(name), "{} mentions {}", path, name);
+            }
+        }
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_only_the_first_view_is_logged() {
+        let workspace = portal_workspace("portal_views");
+        let path = format!("/view/{}", workspace.state.portal_tokens["Bob"]);
+        let history_length = workspace.state.history.len();
+
+        for _ in 0..3 {
+            assert_eq!(request(&workspace, Method::Get, &path).0, 200);
+        }
+
+        let workspace = Workspace::open(&workspace.directory).unwrap();
+        assert_eq!(workspace.state.history.len(), history_length + 1);
+        let views = &workspace.draw(None).unwrap().portal_views;
+        assert_eq!(views.keys().collect::<Vec<_>>(), vec!["Bob"]);
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_portal_link() {
+        assert_eq!(
+            portal_link("https://santa.example.com/", "abc123"),
+            "https://santa.example.com/view/abc123"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 //! A copy of petgraph's ford_fulkerson.rs with the addition of a random shuffle in [`has_augmented_path`].
+//! The source of randomness is passed in so that a seeded draw can be reproduced exactly.
 
 use std::{collections::VecDeque, ops::Sub};
 
@@ -11,7 +12,7 @@ use petgraph::{
     },
     Direction,
 };
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 
 fn residual_capacity<N>(
     network: N,
@@ -28,7 +29,7 @@ where
         flow
     } else if vertex == edge.target() {
         // forward edge
-        return *edge.weight() - flow;
+        *edge.weight() - flow
     } else {
         let end_point = NodeIndexable::to_index(&network, vertex);
         panic!("Illegal endpoint {}", end_point);
@@ -52,14 +53,16 @@ where
 
 /// Tells whether there is an augmented path in the graph.
 /// Randomly shuffles the list of a node's neighbors before adding them to the queue of nodes to visit during BFS.
-fn has_augmented_path<N>(
+fn has_augmented_path<N, R>(
     network: N,
     source: N::NodeId,
     destination: N::NodeId,
     edge_to: &mut [Option<N::EdgeRef>],
     flows: &[N::EdgeWeight],
+    rng: &mut R,
 ) -> bool
 where
+    R: Rng + ?Sized,
     N: NodeCount + IntoEdgesDirected + NodeIndexable + EdgeIndexable + Visitable,
     N::EdgeWeight: Sub<Output = N::EdgeWeight> + PositiveMeasure,
 {
@@ -68,8 +71,6 @@ where
     visited.visit(source);
     queue.push_back(source);
 
-    let mut rng = rand::thread_rng();
-
     while let Some(vertex) = queue.pop_front() {
         let out_edges = network.edges_directed(vertex, Direction::Outgoing);
         let in_edges = network.edges_directed(vertex, Direction::Incoming);
@@ -87,7 +88,7 @@ where
                 mini_queue.push(next);
             }
         }
-        mini_queue.shuffle(&mut rng);
+        mini_queue.shuffle(rng);
         queue.extend(mini_queue);
     }
     false
@@ -148,15 +149,17 @@ where
 ///    (4, 3, 7),
 ///    (4, 5, 4),
 /// ]);
-/// let (max_flow, _) = ford_fulkerson(&graph, source, destination);
+/// let (max_flow, _) = ford_fulkerson(&graph, source, destination, &mut rand::thread_rng());
 /// assert_eq!(23, max_flow);
 /// ```
-pub fn ford_fulkerson<N>(
+pub fn ford_fulkerson<N, R>(
     network: N,
     source: N::NodeId,
     destination: N::NodeId,
+    rng: &mut R,
 ) -> (N::EdgeWeight, Vec<N::EdgeWeight>)
 where
+    R: Rng + ?Sized,
     N: NodeCount
         + EdgeCount
         + IntoEdgesDirected
@@ -169,7 +172,7 @@ where
     let mut edge_to = vec![None; network.node_count()];
     let mut flows = vec![N::EdgeWeight::zero(); network.edge_count()];
     let mut max_flow = N::EdgeWeight::zero();
-    while has_augmented_path(&network, source, destination, &mut edge_to, &flows) {
+    while has_augmented_path(&network, source, destination, &mut edge_to, &flows, rng) {
         let mut path_flow = N::EdgeWeight::max();
 
         // Find the bottleneck capacity of the path
@@ -0,0 +1,125 @@
+//! Writes a participant's file again from a stored draw, e.g. when they lost it, without drawing anything new.
+//!
+//! The file is the same one the participant got with the draw, so the draw, its commitment and everyone else's
+//! files stay as they are.
+
+use crate::workspace::Workspace;
+
+/// Writes the file of the named participant from the draw with the given id (or the latest one) to the output
+/// directory, records the delivery and returns the path of the file. The output directory is only created once
+/// the participant has been found.
+pub fn reissue(
+    workspace: &mut Workspace,
+    draw_id: Option<usize>,
+    name: &str,
+    output_directory: impl FnOnce() -> String,
+) -> Result<String, String> {
+    let draw = workspace.draw(draw_id)?;
+    let permutation = draw
+        .reveal
+        .to_permutation(&workspace.configuration_for_draw(draw))
+        .map_err(|message| format!("The stored draw is not valid: {}", message))?;
+    let assignment = permutation
+        .assignments
+        .iter()
+        .find(|assignment| assignment.sender.name == name)
+        .ok_or_else(|| format!("{:?} is not a participant in draw #{}", name, draw.id))?;
+    let file_path = crate::write_assignment_file(assignment, &draw.reveal, &output_directory())?;
+
+    let draw_id = draw.id;
+    workspace.mark_written(draw_id, name, &file_path);
+    workspace.log(format!(
+        "Reissued the file of {} for draw #{}",
+        name, draw_id
+    ));
+    Ok(file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        commitment::{Recipe, Reveal},
+        configuration::test_support,
+        workspace::{test_support::workspace, DeliveryStatus, DrawKind},
+    };
+
+    #[test]
+    fn test_reissue_only_writes_the_requested_file() {
+        let names = ["Alice", "Bob", "Charlie", "David"];
+        let mut workspace = workspace("reissue", &names);
+        let permutation = test_support::cycle(&test_support::configuration(&names, &[]));
+        let first = workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            None,
+            Reveal::new(
+                &permutation,
+                [0; 32],
+                Recipe::Draw {
+                    method: "flow-network".to_string(),
+                },
+            ),
+            None,
+        );
+        // A later draw, so that reissuing from the first one has to pick it by id
+        let latest = workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            None,
+            Reveal::new(
+                &permutation,
+                [1; 32],
+                Recipe::Draw {
+                    method: "flow-network".to_string(),
+                },
+            ),
+            None,
+        );
+        for name in names {
+            workspace.mark_written(first, name, &format!("sent/{}.txt", name));
+        }
+        let draws_before = workspace.state.draws.clone();
+        let output_directory = workspace.directory.join("reissued");
+        fs::create_dir(&output_directory).unwrap();
+        let output_directory = output_directory.display().to_string();
+
+        let file_path = reissue(&mut workspace, Some(first), "Bob", || {
+            output_directory.clone()
+        })
+        .unwrap();
+
+        let written: Vec<_> = fs::read_dir(&output_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(written, vec!["Bob.txt"]);
+        let contents = fs::read_to_string(&file_path).unwrap();
+        assert!(contents.contains("You are the Secret Santa for Charlie!"));
+        let draw = workspace.draw(Some(first)).unwrap();
+        assert!(contents.contains(&draw.commitment));
+
+        // Only Bob's delivery changed, the draws and their commitments are the same
+        assert!(matches!(
+            &draw.deliveries["Bob"],
+            DeliveryStatus::Written { file_path: path, .. } if *path == file_path
+        ));
+        assert_eq!(workspace.state.draws.len(), draws_before.len());
+        for (after, before) in workspace.state.draws.iter().zip(draws_before.iter()) {
+            assert_eq!(after.commitment, before.commitment);
+            assert_eq!(after.reveal, before.reveal);
+            for name in names {
+                if (after.id, name) != (first, "Bob") {
+                    assert_eq!(after.deliveries[name], before.deliveries[name]);
+                }
+            }
+        }
+        assert_eq!(workspace.draw(None).unwrap().id, latest);
+
+        assert!(reissue(&mut workspace, None, "Eve", || output_directory.clone()).is_err());
+        assert_eq!(fs::read_dir(&output_directory).unwrap().count(), 1);
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+}
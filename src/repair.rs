@@ -0,0 +1,369 @@
+//! Repairs an existing draw after the roster changes, changing as few existing pairs as possible
+//! so that only the people whose assignment actually changed need a new file.
+//!
+//! When participants drop out, every remaining sender keeps their recipient if they can.
+//! Finding the assignment with the fewest changes is a minimum-cost perfect matching:
+//! keeping a previous pair costs 0, any other allowed pair costs 1,
+//! which is solved exactly with the Hungarian algorithm.
+//!
+//! When participants join late, each of them is spliced into the draw by breaking one pair A -> B
+//! into A -> newcomer -> B, which only changes A's assignment.
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    configuration::{Configuration, Participant},
+    permutation::{Assignment, Permutation},
+};
+
+pub struct RepairedDraw {
+    pub permutation: Permutation<Rc<Participant>>,
+    /// Senders whose recipient changed, i.e. the people who need a new file
+    pub changed_senders: Vec<Rc<Participant>>,
+}
+
+/// Solves the assignment problem for a square cost matrix, returning the column assigned to each row.
+/// This is the O(n^3) potentials formulation of the Hungarian algorithm.
+fn min_cost_assignment(costs: &[Vec<i64>]) -> Vec<usize> {
+    let n = costs.len();
+    let infinity = i64::MAX / 2;
+
+    // Everything here is 1-indexed, with row/column 0 acting as a sentinel
+    let mut row_potential = vec![0; n + 1];
+    let mut column_potential = vec![0; n + 1];
+    let mut column_owner = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+
+    for row in 1..=n {
+        column_owner[0] = row;
+        let mut current_column = 0;
+        let mut min_slack = vec![infinity; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[current_column] = true;
+            let current_row = column_owner[current_column];
+            let mut delta = infinity;
+            let mut next_column = 0;
+            for column in 1..=n {
+                if used[column] {
+                    continue;
+                }
+                let slack = costs[current_row - 1][column - 1]
+                    - row_potential[current_row]
+                    - column_potential[column];
+                if slack < min_slack[column] {
+                    min_slack[column] = slack;
+                    way[column] = current_column;
+                }
+                if min_slack[column] < delta {
+                    delta = min_slack[column];
+                    next_column = column;
+                }
+            }
+            for column in 0..=n {
+                if used[column] {
+                    row_potential[column_owner[column]] += delta;
+                    column_potential[column] -= delta;
+                } else {
+                    min_slack[column] -= delta;
+                }
+            }
+            current_column = next_column;
+            if column_owner[current_column] == 0 {
+                break;
+            }
+        }
+
+        // Flip the augmenting path
+        while current_column != 0 {
+            let previous_column = way[current_column];
+            column_owner[current_column] = column_owner[previous_column];
+            current_column = previous_column;
+        }
+    }
+
+    let mut assigned_columns = vec![0; n];
+    for column in 1..=n {
+        assigned_columns[column_owner[column] - 1] = column - 1;
+    }
+    assigned_columns
+}
+
+/// Computes a valid assignment for `configuration` (which no longer contains the dropouts)
+/// that keeps as many pairs of the `previous` draw as possible.
+/// Ties between equally small repairs are broken randomly.
+pub fn repair_without_dropouts<R: Rng>(
+    previous: &Permutation<Rc<Participant>>,
+    configuration: &Configuration,
+    rng: &mut R,
+) -> Result<RepairedDraw, String> {
+    let mut senders = configuration.sorted_participants();
+    let mut recipients = configuration.sorted_participants();
+    senders.shuffle(rng);
+    recipients.shuffle(rng);
+
+    let previous_recipient = |sender: &Rc<Participant>| {
+        previous
+            .assignments
+            .iter()
+            .find(|assignment| &assignment.sender == sender)
+            .map(|assignment| assignment.recipient.clone())
+    };
+
+    // A forbidden pair costs more than changing every single pair, so it is only used when nothing else works
+    let forbidden = senders.len() as i64 + 1;
+    let costs: Vec<Vec<i64>> = senders
+        .iter()
+        .map(|sender| {
+            let kept = previous_recipient(sender);
+            recipients
+                .iter()
+                .map(|recipient| {
+                    if !configuration.allows(sender, recipient) {
+                        forbidden
+                    } else if kept.as_ref() == Some(recipient) {
+                        0
+                    } else {
+                        1
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let assigned_columns = min_cost_assignment(&costs);
+
+    let mut assignments = HashSet::new();
+    let mut changed_senders = Vec::new();
+    for (row, &column) in assigned_columns.iter().enumerate() {
+        if costs[row][column] == forbidden {
+            return Err(format!(
+                "Failed to repair the draw: {} has nobody left to send to without breaking an exclusion or reassigning someone impossibly",
+                senders[row].name
+            ));
+        }
+        if costs[row][column] == 1 {
+            changed_senders.push(senders[row].clone());
+        }
+        assignments.insert(Assignment {
+            sender: senders[row].clone(),
+            recipient: recipients[column].clone(),
+        });
+    }
+    changed_senders.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let permutation = Permutation::try_new(assignments, &configuration.participants)?;
+    configuration.ensure_valid_permutation(&permutation)?;
+
+    Ok(RepairedDraw {
+        permutation,
+        changed_senders,
+    })
+}
+
+/// Splices every joiner into the `previous` draw (made without them) by breaking a random pair A -> B
+/// into A -> joiner -> B, among the pairs where the exclusion constraints allow both new pairs.
+pub fn insert_late_joiners<R: Rng>(
+    previous: &Permutation<Rc<Participant>>,
+    joiners: &[Rc<Participant>],
+    configuration: &Configuration,
+    rng: &mut R,
+) -> Result<RepairedDraw, String> {
+    if let Some(joiner) = joiners
+        .iter()
+        .enumerate()
+        .find_map(|(index, joiner)| joiners[..index].contains(joiner).then_some(joiner))
+    {
+        return Err(format!(
+            "{} is listed as a late joiner more than once",
+            joiner.name
+        ));
+    }
+
+    let mut recipient_of: HashMap<Rc<Participant>, Rc<Participant>> = previous
+        .assignments
+        .iter()
+        .map(|assignment| (assignment.sender.clone(), assignment.recipient.clone()))
+        .collect();
+    let mut changed_senders = Vec::new();
+
+    for joiner in joiners {
+        let mut splice_points: Vec<Rc<Participant>> = recipient_of
+            .iter()
+            .filter(|(sender, recipient)| {
+                configuration.allows(sender, joiner) && configuration.allows(joiner, recipient)
+            })
+            .map(|(sender, _)| sender.clone())
+            .collect();
+        // Sort before choosing so that a seeded insertion is reproducible
+        splice_points.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let sender = splice_points.choose(rng).cloned().ok_or_else(|| {
+            format!(
+                "Failed to insert {}: there is no pair A -> B where A can send to them and they can send to B",
+                joiner.name
+            )
+        })?;
+        let recipient = recipient_of.insert(sender.clone(), joiner.clone()).unwrap();
+        recipient_of.insert(joiner.clone(), recipient);
+
+        for changed in [sender, joiner.clone()] {
+            if !changed_senders.contains(&changed) {
+                changed_senders.push(changed);
+            }
+        }
+    }
+    changed_senders.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let assignments = recipient_of
+        .into_iter()
+        .map(|(sender, recipient)| Assignment { sender, recipient })
+        .collect();
+    let permutation = Permutation::try_new(assignments, &configuration.participants)?;
+    configuration.ensure_valid_permutation(&permutation)?;
+
+    Ok(RepairedDraw {
+        permutation,
+        changed_senders,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::test_support;
+
+    #[test]
+    fn test_min_cost_assignment() {
+        let costs = vec![vec![4, 1, 3], vec![2, 0, 5], vec![3, 2, 2]];
+
+        let assigned_columns = min_cost_assignment(&costs);
+
+        // The optimum is 1 + 2 + 2 = 5
+        assert_eq!(assigned_columns, vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_dropout_only_changes_their_santa() {
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let previous = test_support::cycle(&configuration);
+        let remaining = configuration.without_participants(&HashSet::from(["Charlie".to_string()]));
+
+        let repaired =
+            repair_without_dropouts(&previous, &remaining, &mut rand::thread_rng()).unwrap();
+
+        // Bob was sending to Charlie and now sends to Charlie's recipient, David
+        assert_eq!(
+            repaired
+                .changed_senders
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Bob"]
+        );
+        assert!(repaired
+            .permutation
+            .assignments
+            .iter()
+            .any(|a| a.sender.name == "Bob" && a.recipient.name == "David"));
+    }
+
+    #[test]
+    fn test_dropout_repair_respects_exclusions() {
+        let mut configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let previous = test_support::cycle(&configuration);
+        // Bob can no longer simply take over Charlie's recipient
+        let bob = configuration.sorted_participants()[1].clone();
+        let david = configuration.sorted_participants()[3].clone();
+        configuration
+            .cannot_send_to
+            .get_mut(&david)
+            .unwrap()
+            .insert(bob.clone());
+        let remaining = configuration.without_participants(&HashSet::from(["Charlie".to_string()]));
+
+        let repaired =
+            repair_without_dropouts(&previous, &remaining, &mut rand::thread_rng()).unwrap();
+
+        assert!(repaired.changed_senders.len() >= 2);
+        assert!(remaining
+            .ensure_valid_permutation(&repaired.permutation)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_late_joiner_only_changes_one_existing_pair() {
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let joiners = HashSet::from(["Eve".to_string()]);
+        let existing = configuration.without_participants(&joiners);
+        let previous = test_support::cycle(&existing);
+        let eve = configuration.sorted_participants()[4].clone();
+
+        let inserted = insert_late_joiners(
+            &previous,
+            std::slice::from_ref(&eve),
+            &configuration,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(inserted.changed_senders.len(), 2);
+        assert!(inserted.changed_senders.contains(&eve));
+        let unchanged = previous
+            .assignments
+            .iter()
+            .filter(|a| inserted.permutation.assignments.contains(a))
+            .count();
+        assert_eq!(unchanged, previous.assignments.len() - 1);
+    }
+
+    #[test]
+    fn test_late_joiner_without_splice_point() {
+        let mut configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David"], &[]);
+        let joiners = HashSet::from(["David".to_string()]);
+        let previous = test_support::cycle(&configuration.without_participants(&joiners));
+        let david = configuration.sorted_participants()[3].clone();
+        // Nobody can send to David
+        for participant in configuration.sorted_participants() {
+            configuration
+                .cannot_send_to
+                .get_mut(&david)
+                .unwrap()
+                .insert(participant);
+        }
+
+        assert!(
+            insert_late_joiners(&previous, &[david], &configuration, &mut rand::thread_rng())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_late_joiner_listed_twice() {
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let joiners = HashSet::from(["Eve".to_string()]);
+        let previous = test_support::cycle(&configuration.without_participants(&joiners));
+        let eve = configuration.sorted_participants()[4].clone();
+
+        let error = insert_late_joiners(
+            &previous,
+            &[eve.clone(), eve],
+            &configuration,
+            &mut rand::thread_rng(),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(error, "Eve is listed as a late joiner more than once");
+    }
+}
@@ -0,0 +1,366 @@
+//! Runs a committed draw again from its revealed seed and recipe, to check that the organizer didn't pick the
+//! pairs by hand and only commit to them afterwards.
+//!
+//! Every draw is reproducible from its seed, its recipe and the configuration it was made with, so a reveal
+//! only passes if rerunning the draw gives exactly the revealed assignments.
+//! A repaired draw or one with late joiners is rerun from the previous draw it was made from, whose seed fixes its seed.
+
+use std::rc::Rc;
+
+use clap::ValueEnum;
+
+use crate::{
+    commitment::{Recipe, Reveal},
+    configuration::{Configuration, Participant},
+    matching::{self, MatchingMethod, PermutationBudget},
+    permutation::Permutation,
+    repair, rounds,
+    seed::{self, Seed},
+};
+
+/// The previous draw a repaired draw or one with late joiners was made from, with the configuration it was made with.
+pub struct Parent<'a> {
+    pub reveal: &'a Reveal,
+    pub configuration: &'a Configuration,
+}
+
+/// Checks that `seed` was derived from the previous draw with the given commitment, and returns its assignments.
+fn previous_draw(
+    seed: Seed,
+    commitment: &str,
+    parent: Option<&Parent>,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    let parent = parent.ok_or_else(|| {
+        "The draw was made from a previous draw, which is needed to rerun it".to_string()
+    })?;
+    if parent.reveal.commitment() != commitment {
+        return Err(
+            "The previous draw doesn't match the commitment the draw was made from".to_string(),
+        );
+    }
+    if seed::derived_seed(seed::parse_seed(&parent.reveal.seed)?, commitment) != seed {
+        return Err(
+            "The seed is not the one derived from the previous draw, so it was picked by hand"
+                .to_string(),
+        );
+    }
+    parent
+        .reveal
+        .to_permutation(parent.configuration)
+        .map_err(|message| format!("The previous draw is not valid: {}", message))
+}
+
+/// Makes the draw of the reveal again, with the configuration it was made with and, for a repair or insertion, the previous draw.
+pub fn rerun(
+    reveal: &Reveal,
+    configuration: &Configuration,
+    parent: Option<&Parent>,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    let seed = seed::parse_seed(&reveal.seed)?;
+    let mut rng = seed::rng_from_seed(seed);
+    match &reveal.recipe {
+        Recipe::Draw { method } => {
+            let method = MatchingMethod::from_str(method, false)
+                .map_err(|_| format!("Unknown matching method {:?}", method))?;
+            // The draw succeeded within its budget, so rerunning it never needs more than that
+            let budget = PermutationBudget {
+                max_attempts: u64::MAX,
+                time_limit: std::time::Duration::MAX,
+            };
+            matching::generate_permutation(&method, configuration, false, &budget, &mut rng)
+        }
+        Recipe::Repair {
+            parent: commitment, ..
+        } => {
+            let previous = previous_draw(seed, commitment, parent)?;
+            repair::repair_without_dropouts(&previous, configuration, &mut rng)
+                .map(|repaired| repaired.permutation)
+        }
+        Recipe::Insert {
+            parent: commitment,
+            added,
+        } => {
+            let previous = previous_draw(seed, commitment, parent)?;
+            let joiners = added
+                .iter()
+                .map(|name| {
+                    configuration
+                        .participants
+                        .iter()
+                        .find(|participant| &participant.name == name)
+                        .cloned()
+                        .ok_or_else(|| {
+                            format!("{:?} is not a participant in the configuration", name)
+                        })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            repair::insert_late_joiners(&previous, &joiners, configuration, &mut rng)
+                .map(|inserted| inserted.permutation)
+        }
+        Recipe::Round {
+            round,
+            rounds,
+            min_cycle_length,
+            no_mutual,
+        } => {
+            if *round == 0 || round > rounds {
+                return Err(format!("There is no round {} of {}", round, rounds));
+            }
+            // Rounds are drawn together, so every round before this one is drawn again too
+            let constraints = rounds::RoundConstraints {
+                min_cycle_length: *min_cycle_length,
+                no_mutual: *no_mutual,
+            };
+            let mut drawn = rounds::draw_rounds(configuration, *rounds, &constraints, &mut rng)?;
+            Ok(drawn.swap_remove(round - 1))
+        }
+    }
+}
+
+fn sorted_pairs<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<(&'a str, &'a str)> {
+    let mut pairs: Vec<_> = pairs.collect();
+    pairs.sort();
+    pairs
+}
+
+/// Reruns the draw and checks that it gives exactly the revealed assignments.
+pub fn verify_rerun(
+    reveal: &Reveal,
+    configuration: &Configuration,
+    parent: Option<&Parent>,
+) -> Result<(), String> {
+    let permutation = rerun(reveal, configuration, parent)?;
+    let rerun_pairs = sorted_pairs(permutation.assignments.iter().map(|assignment| {
+        (
+            assignment.sender.name.as_str(),
+            assignment.recipient.name.as_str(),
+        )
+    }));
+    let revealed_pairs = sorted_pairs(
+        reveal
+            .assignments
+            .iter()
+            .map(|assignment| (assignment.sender.as_str(), assignment.recipient.as_str())),
+    );
+    if rerun_pairs != revealed_pairs {
+        return Err(
+            "Rerunning the draw from the revealed seed gives different assignments, so the revealed ones weren't drawn with this seed"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{commitment::RevealedAssignment, configuration::test_support};
+
+    fn draw(configuration: &Configuration, method: &str) -> Reveal {
+        let seed = [5; 32];
+        let permutation = matching::generate_permutation(
+            &MatchingMethod::from_str(method, false).unwrap(),
+            configuration,
+            false,
+            &PermutationBudget::default(),
+            &mut seed::rng_from_seed(seed),
+        )
+        .unwrap();
+        Reveal::new(
+            &permutation,
+            seed,
+            Recipe::Draw {
+                method: method.to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_rerun_matches_the_draw() {
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David", "Eve", "Frank"],
+            &[("Alice", "Bob")],
+        );
+        for method in ["permutation", "flow-network", "uniform"] {
+            assert!(verify_rerun(&draw(&configuration, method), &configuration, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rerun_rejects_hand_picked_pairs() {
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let reveal = draw(&configuration, "flow-network");
+
+        // A different valid assignment, committed with the same seed: every cycle of the draw reversed.
+        // With five people at least one cycle is longer than two, so some pair changes.
+        let mut rigged = reveal.clone();
+        rigged.assignments = reveal
+            .assignments
+            .iter()
+            .map(|assignment| RevealedAssignment {
+                sender: assignment.recipient.clone(),
+                recipient: assignment.sender.clone(),
+                salt: assignment.salt.clone(),
+            })
+            .collect();
+        rigged.assignments.sort_by(|a, b| a.sender.cmp(&b.sender));
+        assert_ne!(rigged.assignments, reveal.assignments);
+        assert!(rigged.to_permutation(&configuration).is_ok());
+
+        assert!(verify_rerun(&rigged, &configuration, None).is_err());
+    }
+
+    #[test]
+    fn test_rerun_rounds() {
+        let configuration = test_support::numbered_configuration(6);
+        let seed = [5; 32];
+        let constraints = rounds::RoundConstraints {
+            min_cycle_length: 3,
+            no_mutual: false,
+        };
+        let drawn = rounds::draw_rounds(
+            &configuration,
+            2,
+            &constraints,
+            &mut seed::rng_from_seed(seed),
+        )
+        .unwrap();
+        let reveal = |round: usize, permutation| {
+            Reveal::new(
+                permutation,
+                seed,
+                Recipe::Round {
+                    round,
+                    rounds: 2,
+                    min_cycle_length: 3,
+                    no_mutual: false,
+                },
+            )
+        };
+
+        assert!(verify_rerun(&reveal(1, &drawn[0]), &configuration, None).is_ok());
+        assert!(verify_rerun(&reveal(2, &drawn[1]), &configuration, None).is_ok());
+        // The same seed gives every round, but a reveal only matches the round it names
+        assert!(verify_rerun(&reveal(2, &drawn[0]), &configuration, None).is_err());
+        assert_eq!(
+            verify_rerun(&reveal(0, &drawn[0]), &configuration, None).unwrap_err(),
+            "There is no round 0 of 2"
+        );
+        assert_eq!(
+            verify_rerun(&reveal(3, &drawn[0]), &configuration, None).unwrap_err(),
+            "There is no round 3 of 2"
+        );
+    }
+
+    #[test]
+    fn test_rerun_repaired_draw() {
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "David", "Eve"], &[]);
+        let previous = draw(&configuration, "flow-network");
+        let parent = Parent {
+            reveal: &previous,
+            configuration: &configuration,
+        };
+        let remaining = configuration.without_participants(&HashSet::from(["Charlie".to_string()]));
+        let repair = |seed: Seed| {
+            let repaired = repair::repair_without_dropouts(
+                &previous.to_permutation(&configuration).unwrap(),
+                &remaining,
+                &mut seed::rng_from_seed(seed),
+            )
+            .unwrap();
+            Reveal::new(
+                &repaired.permutation,
+                seed,
+                Recipe::Repair {
+                    parent: previous.commitment(),
+                    removed: vec!["Charlie".to_string()],
+                },
+            )
+        };
+
+        let seed = seed::derived_seed(
+            seed::parse_seed(&previous.seed).unwrap(),
+            &previous.commitment(),
+        );
+        let repaired = repair(seed);
+        let commitment = repaired.commitment();
+        assert!(crate::commitment::verify_reveal(&commitment, &repaired, &remaining).is_ok());
+        assert!(verify_rerun(&repaired, &remaining, Some(&parent)).is_ok());
+        assert!(verify_rerun(&repaired, &remaining, None).is_err());
+
+        // Another previous draw, or a seed the organizer picked, doesn't pass
+        let other = draw(
+            &test_support::configuration(
+                &["Alice", "Bob", "Charlie", "David", "Eve"],
+                &[("Alice", "Bob")],
+            ),
+            "uniform",
+        );
+        let other_parent = Parent {
+            reveal: &other,
+            configuration: &configuration,
+        };
+        assert!(verify_rerun(&repaired, &remaining, Some(&other_parent)).is_err());
+        assert!(verify_rerun(&repair([9; 32]), &remaining, Some(&parent)).is_err());
+    }
+
+    #[test]
+    fn test_rerun_draw_with_late_joiners() {
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David", "Eve", "Frank"],
+            &[("Eve", "Alice")],
+        );
+        let joiners = ["Frank".to_string(), "Eve".to_string()];
+        let earlier = configuration.without_participants(&joiners.iter().cloned().collect());
+        let previous = draw(&earlier, "flow-network");
+        let parent = Parent {
+            reveal: &previous,
+            configuration: &earlier,
+        };
+        let insert = |seed: Seed| {
+            let inserted = repair::insert_late_joiners(
+                &previous.to_permutation(&earlier).unwrap(),
+                &joiners
+                    .iter()
+                    .map(|name| {
+                        configuration
+                            .participants
+                            .iter()
+                            .find(|participant| &participant.name == name)
+                            .unwrap()
+                            .clone()
+                    })
+                    .collect::<Vec<_>>(),
+                &configuration,
+                &mut seed::rng_from_seed(seed),
+            )
+            .unwrap();
+            Reveal::new(
+                &inserted.permutation,
+                seed,
+                Recipe::Insert {
+                    parent: previous.commitment(),
+                    added: joiners.to_vec(),
+                },
+            )
+        };
+
+        let inserted = insert(seed::derived_seed(
+            seed::parse_seed(&previous.seed).unwrap(),
+            &previous.commitment(),
+        ));
+        assert!(crate::commitment::verify_reveal(
+            &inserted.commitment(),
+            &inserted,
+            &configuration
+        )
+        .is_ok());
+        assert!(verify_rerun(&inserted, &configuration, Some(&parent)).is_ok());
+        assert!(verify_rerun(&inserted, &configuration, None).is_err());
+        assert!(verify_rerun(&insert([9; 32]), &configuration, Some(&parent)).is_err());
+    }
+}
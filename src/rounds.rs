@@ -0,0 +1,300 @@
+//! Several rounds of gifts, e.g. one per week of an advent event, where no santa gets the same recipient twice.
+//!
+//! Rounds are drawn one after the other with the flow network, each on the configuration minus the pairs of the
+//! earlier rounds. A round that breaks the cycle constraints is drawn again. Earlier rounds can use up pairs that a
+//! later round needs, so if a round can't be drawn at all, every round is drawn again from the start.
+
+use std::rc::Rc;
+
+use rand::Rng;
+
+use crate::{
+    configuration::{Configuration, Participant},
+    flow_network,
+    permutation::Permutation,
+};
+
+/// How often a round is drawn again when its cycles are too short
+const ATTEMPTS_PER_ROUND: usize = 200;
+/// How often all rounds are drawn again from the start
+const RESTARTS: usize = 20;
+
+/// Constraints every round has to satisfy on its own.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundConstraints {
+    /// Smallest number of people in a gift-giving cycle
+    pub min_cycle_length: usize,
+    /// Whether two people may send to each other in the same round
+    pub no_mutual: bool,
+}
+
+impl RoundConstraints {
+    fn shortest_cycle(&self) -> usize {
+        self.min_cycle_length
+            .max(if self.no_mutual { 3 } else { 2 })
+    }
+
+    pub fn allows(&self, permutation: &Permutation<Rc<Participant>>) -> bool {
+        permutation
+            .cycles()
+            .iter()
+            .all(|cycle| cycle.len() >= self.shortest_cycle())
+    }
+}
+
+/// Fails early for participants who can't have a different recipient or santa in every round.
+fn ensure_enough_options(configuration: &Configuration, rounds: usize) -> Result<(), String> {
+    let participants = configuration.sorted_participants();
+    let mut problems = Vec::new();
+    for participant in participants.iter() {
+        let recipients = participants
+            .iter()
+            .filter(|recipient| configuration.allows(participant, recipient))
+            .count();
+        if recipients < rounds {
+            problems.push(format!(
+                "{} can only send to {} people",
+                participant.name, recipients
+            ));
+        }
+        let senders = participants
+            .iter()
+            .filter(|sender| configuration.allows(sender, participant))
+            .count();
+        if senders < rounds {
+            problems.push(format!(
+                "{} can only receive from {} people",
+                participant.name, senders
+            ));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} rounds are impossible: {}",
+            rounds,
+            problems.join(", ")
+        ))
+    }
+}
+
+fn try_draw_rounds<R: Rng>(
+    configuration: &Configuration,
+    rounds: usize,
+    constraints: &RoundConstraints,
+    rng: &mut R,
+) -> Result<Vec<Permutation<Rc<Participant>>>, String> {
+    let mut drawn: Vec<Permutation<Rc<Participant>>> = Vec::new();
+    let mut used_pairs = Vec::new();
+    for round in 1..=rounds {
+        let remaining = configuration.without_pairs(&used_pairs);
+        let mut permutation = None;
+        for _ in 0..ATTEMPTS_PER_ROUND {
+            let candidate = flow_network::try_generate_assignments(&remaining, false, rng)
+                .and_then(|assignments| {
+                    Permutation::try_new(assignments, &configuration.participants)
+                })
+                .map_err(|message| format!("Round {}: {}", round, message))?;
+            if constraints.allows(&candidate) {
+                permutation = Some(candidate);
+                break;
+            }
+        }
+        let permutation = permutation.ok_or_else(|| {
+            format!(
+                "Round {}: no assignment with cycles of at least {} people found",
+                round,
+                constraints.shortest_cycle()
+            )
+        })?;
+        used_pairs.extend(
+            permutation
+                .assignments
+                .iter()
+                .map(|assignment| (assignment.sender.clone(), assignment.recipient.clone())),
+        );
+        drawn.push(permutation);
+    }
+    Ok(drawn)
+}
+
+/// Draws `rounds` valid assignments where no sender has the same recipient twice, each satisfying the constraints.
+pub fn draw_rounds<R: Rng>(
+    configuration: &Configuration,
+    rounds: usize,
+    constraints: &RoundConstraints,
+    rng: &mut R,
+) -> Result<Vec<Permutation<Rc<Participant>>>, String> {
+    if constraints.shortest_cycle() > configuration.participants.len() {
+        return Err(format!(
+            "Cycles of at least {} people are impossible with {} participants",
+            constraints.shortest_cycle(),
+            configuration.participants.len()
+        ));
+    }
+    ensure_enough_options(configuration, rounds)?;
+
+    let mut last_error = String::new();
+    for _ in 0..RESTARTS {
+        match try_draw_rounds(configuration, rounds, constraints, rng) {
+            Ok(drawn) => return Ok(drawn),
+            Err(message) => last_error = message,
+        }
+    }
+    Err(format!(
+        "Gave up after drawing all rounds {} times, the exclusions are probably too tight for {} rounds. Last failure: {}",
+        RESTARTS, rounds, last_error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{configuration::test_support, seed};
+
+    #[test]
+    fn test_rounds_are_disjoint() {
+        let configuration = test_support::numbered_configuration(6);
+        let constraints = RoundConstraints {
+            min_cycle_length: 2,
+            no_mutual: true,
+        };
+
+        let drawn = draw_rounds(
+            &configuration,
+            3,
+            &constraints,
+            &mut seed::rng_from_seed([7; 32]),
+        )
+        .unwrap();
+
+        assert_eq!(drawn.len(), 3);
+        let mut pairs = HashSet::new();
+        for permutation in drawn.iter() {
+            configuration.ensure_valid_permutation(permutation).unwrap();
+            assert!(permutation.cycles().iter().all(|cycle| cycle.len() >= 3));
+            for assignment in permutation.assignments.iter() {
+                assert!(pairs.insert((assignment.sender.clone(), assignment.recipient.clone())));
+            }
+        }
+
+        // Everyone only has two possible recipients
+        assert!(draw_rounds(
+            &test_support::numbered_configuration(3),
+            3,
+            &constraints,
+            &mut seed::rng_from_seed([7; 32])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_not_enough_options() {
+        // Alice can't send to Bob and Charlie can't send to Alice, so both only have two options
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "David"],
+            &[("Alice", "Bob"), ("Charlie", "Alice")],
+        );
+
+        assert!(ensure_enough_options(&configuration, 2).is_ok());
+        assert_eq!(
+            ensure_enough_options(&configuration, 3).unwrap_err(),
+            "3 rounds are impossible: Alice can only send to 2 people, Alice can only receive from 2 people, \
+             Bob can only receive from 2 people, Charlie can only send to 2 people"
+        );
+    }
+
+    #[test]
+    fn test_cycles_longer_than_the_roster() {
+        let error = draw_rounds(
+            &test_support::numbered_configuration(3),
+            1,
+            &RoundConstraints {
+                min_cycle_length: 4,
+                no_mutual: false,
+            },
+            &mut seed::rng_from_seed([7; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Cycles of at least 4 people are impossible with 3 participants"
+        );
+
+        // Without mutual pairs, two people can't form any cycle
+        let error = draw_rounds(
+            &test_support::numbered_configuration(2),
+            1,
+            &RoundConstraints {
+                min_cycle_length: 2,
+                no_mutual: true,
+            },
+            &mut seed::rng_from_seed([7; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Cycles of at least 3 people are impossible with 2 participants"
+        );
+    }
+
+    #[test]
+    fn test_restarts_after_a_dead_end() {
+        // Four rounds of 5-cycles use up every pair, so an unlucky early round leaves no 5-cycle for a later one
+        let configuration = test_support::numbered_configuration(5);
+        let constraints = RoundConstraints {
+            min_cycle_length: 5,
+            no_mutual: true,
+        };
+        let dead_end = (0..=u8::MAX)
+            .find(|&seed| {
+                try_draw_rounds(
+                    &configuration,
+                    4,
+                    &constraints,
+                    &mut seed::rng_from_seed([seed; 32]),
+                )
+                .is_err()
+            })
+            .expect("some seed should run into a dead end");
+
+        let drawn = draw_rounds(
+            &configuration,
+            4,
+            &constraints,
+            &mut seed::rng_from_seed([dead_end; 32]),
+        )
+        .unwrap();
+
+        let mut pairs = HashSet::new();
+        for permutation in drawn.iter() {
+            assert!(constraints.allows(permutation));
+            for assignment in permutation.assignments.iter() {
+                assert!(pairs.insert((assignment.sender.clone(), assignment.recipient.clone())));
+            }
+        }
+        assert_eq!(pairs.len(), 20);
+    }
+
+    #[test]
+    fn test_gives_up_after_restarts() {
+        // Every person has three recipients, but two disjoint 4-cycles leave only mutual pairs for the third round
+        let error = draw_rounds(
+            &test_support::numbered_configuration(4),
+            3,
+            &RoundConstraints {
+                min_cycle_length: 2,
+                no_mutual: true,
+            },
+            &mut seed::rng_from_seed([7; 32]),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert!(error.starts_with("Gave up after drawing all rounds 20 times"));
+    }
+}
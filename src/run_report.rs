@@ -0,0 +1,137 @@
+//! A machine-readable summary of a draw, printed instead of the progress messages with `--output-format json`.
+//!
+//! Pairings are left out unless `--include-pairings` is given, so that the report can be stored as a CI artifact.
+
+use serde::Serialize;
+
+use crate::{commitment::Reveal, draw_statistics::DrawStatistics};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeedSource {
+    /// Given with `--seed`
+    Argument,
+    /// Derived from the participants' random strings
+    Participants,
+    Random,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Pairing {
+    pub sender: String,
+    pub recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub success: bool,
+    /// Why the draw failed
+    pub error: Option<String>,
+    /// Sorted by name
+    pub participants: Vec<String>,
+    pub warnings: Vec<String>,
+    pub method: String,
+    /// Hex-encoded seed, rerun this exact draw with `--seed`
+    pub seed: Option<String>,
+    pub seed_source: Option<SeedSource>,
+    pub commitment: Option<String>,
+    pub output_directory: Option<String>,
+    pub written_files: Vec<String>,
+    /// Id of the draw in the workspace, if the draw was stored in one
+    pub draw_id: Option<usize>,
+    pub statistics: Option<DrawStatistics>,
+    /// Only present with `--include-pairings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pairings: Option<Vec<Pairing>>,
+    pub elapsed_seconds: f64,
+}
+
+impl RunReport {
+    pub fn new(participants: Vec<String>, method: String) -> RunReport {
+        RunReport {
+            success: false,
+            error: None,
+            participants,
+            warnings: Vec::new(),
+            method,
+            seed: None,
+            seed_source: None,
+            commitment: None,
+            output_directory: None,
+            written_files: Vec::new(),
+            draw_id: None,
+            statistics: None,
+            pairings: None,
+            elapsed_seconds: 0.0,
+        }
+    }
+
+    pub fn set_pairings(&mut self, reveal: &Reveal) {
+        self.pairings = Some(
+            reveal
+                .assignments
+                .iter()
+                .map(|assignment| Pairing {
+                    sender: assignment.sender.clone(),
+                    recipient: assignment.recipient.clone(),
+                })
+                .collect(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commitment::Recipe, configuration::test_support};
+
+    fn report() -> RunReport {
+        RunReport::new(
+            vec![
+                "Alice".to_string(),
+                "Bob".to_string(),
+                "Charlie".to_string(),
+            ],
+            "flow-network".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_failed_report() {
+        let mut report = report();
+        report.error = Some("No valid assignment exists".to_string());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["error"], "No valid assignment exists");
+        assert_eq!(json["participants"].as_array().unwrap().len(), 3);
+        assert!(json.get("pairings").is_none());
+    }
+
+    #[test]
+    fn test_pairings_only_when_included() {
+        let configuration = test_support::configuration(&["Alice", "Bob", "Charlie"], &[]);
+        let reveal = Reveal::new(
+            &test_support::cycle(&configuration),
+            [0; 32],
+            Recipe::Draw {
+                method: "flow-network".to_string(),
+            },
+        );
+        let mut report = report();
+        report.success = true;
+        report.commitment = Some(reveal.commitment());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["success"], true);
+        assert!(json["error"].is_null());
+        assert!(json.get("pairings").is_none());
+
+        report.set_pairings(&reveal);
+        let json = serde_json::to_value(&report).unwrap();
+        let pairings = json["pairings"].as_array().unwrap();
+        assert_eq!(pairings.len(), 3);
+        assert_eq!(pairings[0]["sender"], "Alice");
+        assert_eq!(pairings[0]["recipient"], "Bob");
+    }
+}
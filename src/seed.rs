@@ -0,0 +1,152 @@
+//! Derives the seed that drives the random draw from strings contributed by the participants themselves.
+//!
+//! Every participant may fill in a random string on the sign-up form.
+//! The seed is the SHA-256 hash of every (name, contribution) pair, sorted by name and length-prefixed:
+//!
+//! `seed = SHA256("secret-santa-seed-v1" || len(name_1) || name_1 || len(contribution_1) || contribution_1 || ...)`
+//!
+//! As long as a single participant's contribution is unpredictable, so is the seed,
+//! and anyone holding the contributions can recompute it and rerun the exact same draw.
+//!
+//! A draw made from a previous one (a repair or an insertion of late joiners) doesn't get a new seed from the
+//! organizer. Its seed is derived from the seed and the commitment of the previous draw instead:
+//!
+//! `seed = SHA256("secret-santa-derived-seed-v1" || previous seed || previous commitment)`
+
+use std::fmt::Write;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+const DOMAIN_SEPARATOR: &[u8] = b"secret-santa-seed-v1";
+const DERIVED_DOMAIN_SEPARATOR: &[u8] = b"secret-santa-derived-seed-v1";
+
+pub type Seed = [u8; 32];
+
+pub struct SeedDerivation {
+    /// (name, contribution) pairs, sorted by name
+    pub contributions: Vec<(String, String)>,
+    pub seed: Seed,
+}
+
+impl SeedDerivation {
+    pub fn from_contributions(mut contributions: Vec<(String, String)>) -> SeedDerivation {
+        contributions.sort();
+
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN_SEPARATOR);
+        for (name, contribution) in contributions.iter() {
+            hasher.update((name.len() as u64).to_be_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update((contribution.len() as u64).to_be_bytes());
+            hasher.update(contribution.as_bytes());
+        }
+
+        SeedDerivation {
+            contributions,
+            seed: hasher.finalize().into(),
+        }
+    }
+
+    pub fn has_contributions(&self) -> bool {
+        self.contributions
+            .iter()
+            .any(|(_, contribution)| !contribution.is_empty())
+    }
+
+    /// Fails unless the hex-encoded `revealed_seed` is the one derived from the contributions.
+    pub fn ensure_derived_seed(&self, revealed_seed: &str) -> Result<(), String> {
+        if hex::encode(self.seed) == revealed_seed {
+            Ok(())
+        } else {
+            Err(format!(
+                "the revealed seed {} is not the one derived from the participants' random strings ({})",
+                revealed_seed,
+                hex::encode(self.seed)
+            ))
+        }
+    }
+
+    /// A human-readable description of how the seed was computed, so that anyone can recompute it.
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        writeln!(
+            description,
+            "seed = SHA256(\"{}\" || for each participant sorted by name: u64_be(len(name)) || name || u64_be(len(contribution)) || contribution)",
+            String::from_utf8_lossy(DOMAIN_SEPARATOR)
+        )
+        .unwrap();
+        writeln!(description).unwrap();
+        for (name, contribution) in self.contributions.iter() {
+            writeln!(description, "{:?}: {:?}", name, contribution).unwrap();
+        }
+        writeln!(description).unwrap();
+        writeln!(description, "seed = {}", hex::encode(self.seed)).unwrap();
+        description
+    }
+}
+
+/// Picks a seed without any participant input, for sign-up forms that don't collect contributions.
+pub fn random_seed() -> Seed {
+    let mut seed = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut seed);
+    seed
+}
+
+/// The seed of a draw made from the previous draw with the given seed and commitment.
+pub fn derived_seed(previous_seed: Seed, previous_commitment: &str) -> Seed {
+    let mut hasher = Sha256::new();
+    hasher.update(DERIVED_DOMAIN_SEPARATOR);
+    hasher.update(previous_seed);
+    hasher.update(previous_commitment.as_bytes());
+    hasher.finalize().into()
+}
+
+pub fn parse_seed(hex_string: &str) -> Result<Seed, String> {
+    hex::decode(hex_string.trim())
+        .map_err(|error| format!("Invalid seed {:?}: {}", hex_string, error))?
+        .try_into()
+        .map_err(|_| format!("Expected a 32 byte seed, got {:?}", hex_string))
+}
+
+pub fn rng_from_seed(seed: Seed) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_does_not_depend_on_submission_order() {
+        let forwards = SeedDerivation::from_contributions(vec![
+            ("Alice".to_string(), "correct horse".to_string()),
+            ("Bob".to_string(), "battery staple".to_string()),
+        ]);
+        let backwards = SeedDerivation::from_contributions(vec![
+            ("Bob".to_string(), "battery staple".to_string()),
+            ("Alice".to_string(), "correct horse".to_string()),
+        ]);
+
+        assert_eq!(forwards.seed, backwards.seed);
+    }
+
+    #[test]
+    fn test_field_boundaries_are_unambiguous() {
+        let first = SeedDerivation::from_contributions(vec![("Al".to_string(), "ice".to_string())]);
+        let second =
+            SeedDerivation::from_contributions(vec![("Ali".to_string(), "ce".to_string())]);
+
+        assert_ne!(first.seed, second.seed);
+    }
+
+    #[test]
+    fn test_derived_seed_depends_on_the_previous_draw() {
+        let seed = derived_seed([1; 32], "commitment");
+
+        assert_eq!(seed, derived_seed([1; 32], "commitment"));
+        assert_ne!(seed, derived_seed([2; 32], "commitment"));
+        assert_ne!(seed, derived_seed([1; 32], "other commitment"));
+    }
+}
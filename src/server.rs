@@ -0,0 +1,368 @@
+//! A small local HTTP server hosting the sign-up form, so that an event doesn't have to depend on Google Forms.
+//!
+//! Sign-ups are written straight into the event workspace, in the same shape as the responses imported from a `.csv` file.
+//! The workspace is read again for every request, so other commands can keep working on it while the server runs.
+
+use std::{
+    fmt::Write,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{form_submission::FormSubmission, workspace::Workspace};
+
+/// Sign-up forms are small, anything larger than this is rejected
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+/// How many names can be entered for each kind of exclusion
+const EXCLUSION_FIELDS: usize = 5;
+
+pub type HtmlResponse = Response<Cursor<Vec<u8>>>;
+
+/// Escapes text for HTML, and for XML such as SVG and GraphML.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Parses a deadline given as RFC 3339 or as local "YYYY-MM-DD HH:MM", returning it as RFC 3339.
+pub fn parse_deadline(deadline: &str) -> Result<String, String> {
+    if let Ok(deadline) = DateTime::parse_from_rfc3339(deadline) {
+        return Ok(deadline.to_rfc3339());
+    }
+    NaiveDateTime::parse_from_str(deadline, "%Y-%m-%d %H:%M")
+        .ok()
+        .and_then(|deadline| Local.from_local_datetime(&deadline).single())
+        .map(|deadline| deadline.to_rfc3339())
+        .ok_or_else(|| {
+            format!(
+                "Invalid deadline {:?}, expected RFC 3339 or \"YYYY-MM-DD HH:MM\"",
+                deadline
+            )
+        })
+}
+
+fn deadline_has_passed(workspace: &Workspace) -> bool {
+    workspace
+        .state
+        .config
+        .sign_up_deadline
+        .as_deref()
+        .and_then(|deadline| DateTime::parse_from_rfc3339(deadline).ok())
+        .is_some_and(|deadline| Local::now() > deadline)
+}
+
+pub fn html_response(status: u16, body: String) -> HtmlResponse {
+    Response::from_string(body)
+        .with_status_code(StatusCode(status))
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..]).unwrap(),
+        )
+}
+
+pub fn html_page(title: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n  <meta charset=\"utf-8\">\n  <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n  <title>{0}</title>\n  <style>body {{ font-family: sans-serif; max-width: 40em; margin: 2em auto; padding: 0 1em; }} label {{ display: block; margin-top: 1em; font-weight: bold; }} input, textarea {{ width: 100%; box-sizing: border-box; }} .error {{ color: #c62828; }} .success {{ color: #2e7d32; }}</style>\n</head>\n<body>\n  <h1>{0}</h1>\n{1}</body>\n</html>\n",
+        escape_html(title),
+        content
+    )
+}
+
+pub fn read_form(request: &mut Request) -> Result<Vec<(String, String)>, String> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_LENGTH + 1)
+        .read_to_string(&mut body)
+        .map_err(|error| format!("Failed to read the request: {}", error))?;
+    if body.len() as u64 > MAX_BODY_LENGTH {
+        return Err("The request is too large".to_string());
+    }
+    Ok(form_urlencoded::parse(body.as_bytes())
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect())
+}
+
+fn field(form: &[(String, String)], key: &str) -> String {
+    form.iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn names(form: &[(String, String)], key: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, value) in form.iter().filter(|(name, _)| name == key) {
+        let value = value.trim();
+        if !value.is_empty() && !names.iter().any(|name| name == value) {
+            names.push(value.to_string());
+        }
+    }
+    names
+}
+
+/// Names end up in file names, so anything that could point outside the output directory is rejected.
+fn ensure_valid_name(name: &str) -> Result<(), String> {
+    if name.contains(['/', '\\'])
+        || name.contains("..")
+        || name.chars().any(|character| character.is_control())
+    {
+        return Err(
+            "Names can't contain slashes, backslashes, \"..\" or control characters.".to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Turns the submitted form into a `FormSubmission`, or explains what is missing.
+pub fn submission_from_form(form: &[(String, String)]) -> Result<FormSubmission, String> {
+    let submission = FormSubmission {
+        timestamp: Local::now().format("%-m/%-d/%Y %H:%M:%S").to_string(),
+        name: field(form, "name"),
+        discord_handle: field(form, "discord_handle"),
+        cannot_send_to_submitter: names(form, "cannot_send_to_submitter"),
+        cannot_receive_from_submitter: names(form, "cannot_receive_from_submitter"),
+        mailing_info: field(form, "mailing_info"),
+        interests: field(form, "interests"),
+        anything_else: field(form, "anything_else"),
+        random_string: field(form, "random_string"),
+    };
+    if submission.name.is_empty() {
+        return Err("Please fill in your name.".to_string());
+    }
+    if submission.mailing_info.is_empty() {
+        return Err("Please fill in your mailing info.".to_string());
+    }
+    ensure_valid_name(&submission.name)?;
+    // Names are joined with ", " when exported, so they can't contain it
+    if submission.name.contains(", ")
+        || submission
+            .cannot_send_to_submitter
+            .iter()
+            .chain(submission.cannot_receive_from_submitter.iter())
+            .any(|name| name.contains(", "))
+    {
+        return Err("Names can't contain \", \", enter one name per field.".to_string());
+    }
+    Ok(submission)
+}
+
+fn sign_up_page(workspace: &Workspace, message: Option<(&str, &str)>) -> String {
+    let mut content = String::new();
+    if let Some((class, text)) = message {
+        writeln!(
+            content,
+            "  <p class=\"{}\">{}</p>",
+            class,
+            escape_html(text)
+        )
+        .unwrap();
+    }
+    if let Some(deadline) = &workspace.state.config.sign_up_deadline {
+        writeln!(
+            content,
+            "  <p>Sign-ups close at {}.</p>",
+            escape_html(deadline)
+        )
+        .unwrap();
+    }
+    if deadline_has_passed(workspace) {
+        writeln!(content, "  <p>Sign-ups are closed.</p>").unwrap();
+        return html_page(&workspace.state.config.name, &content);
+    }
+
+    writeln!(content, "  <datalist id=\"roster\">").unwrap();
+    for submission in workspace.active_submissions() {
+        writeln!(
+            content,
+            "    <option value=\"{}\">",
+            escape_html(&submission.name)
+        )
+        .unwrap();
+    }
+    writeln!(content, "  </datalist>").unwrap();
+
+    writeln!(content, "  <form method=\"post\" action=\"/sign-up\">").unwrap();
+    for (name, label, multiline) in [
+        ("name", "Who are you?", false),
+        ("discord_handle", "Your Discord Handle", false),
+        ("mailing_info", "Your Mailing Info", true),
+        ("interests", "Interests", true),
+    ] {
+        writeln!(content, "    <label for=\"{0}\">{1}</label>", name, label).unwrap();
+        if multiline {
+            writeln!(
+                content,
+                "    <textarea id=\"{0}\" name=\"{0}\" rows=\"3\"></textarea>",
+                name
+            )
+            .unwrap();
+        } else {
+            writeln!(content, "    <input id=\"{0}\" name=\"{0}\">", name).unwrap();
+        }
+    }
+    for (name, label) in [
+        (
+            "cannot_send_to_submitter",
+            "Who shouldn't be your Secret Santa?",
+        ),
+        (
+            "cannot_receive_from_submitter",
+            "Who shouldn't you be the Secret Santa for?",
+        ),
+    ] {
+        writeln!(content, "    <label>{}</label>", label).unwrap();
+        for _ in 0..EXCLUSION_FIELDS {
+            writeln!(
+                content,
+                "    <input name=\"{}\" list=\"roster\" autocomplete=\"off\">",
+                name
+            )
+            .unwrap();
+        }
+    }
+    for (name, label) in [
+        ("anything_else", "Anything Else?"),
+        (
+            "random_string",
+            "Your Random String (type anything, it makes the draw more random)",
+        ),
+    ] {
+        writeln!(content, "    <label for=\"{0}\">{1}</label>", name, label).unwrap();
+        writeln!(content, "    <input id=\"{0}\" name=\"{0}\">", name).unwrap();
+    }
+    writeln!(
+        content,
+        "    <p><button type=\"submit\">Sign up</button></p>"
+    )
+    .unwrap();
+    writeln!(content, "  </form>").unwrap();
+
+    html_page(&workspace.state.config.name, &content)
+}
+
+fn handle_sign_up(request: &mut Request, workspace_directory: &Path) -> Result<String, String> {
+    let form = read_form(request)?;
+    let mut workspace = Workspace::open(workspace_directory)?;
+    if deadline_has_passed(&workspace) {
+        return Err("Sorry, sign-ups are closed.".to_string());
+    }
+    let submission = submission_from_form(&form)?;
+
+    let name = submission.name.clone();
+    if workspace.import_submissions(vec![submission]).is_empty() {
+        return Err(format!(
+            "{} has already signed up. Ask the organizer if you need to change your answers.",
+            name
+        ));
+    }
+    workspace.log(format!("{} signed up through the sign-up server", name));
+    workspace.save()?;
+    eprintln!("{:?} signed up.", name);
+    Ok(format!("Thanks for signing up, {}!", name))
+}
+
+fn handle_request(request: &mut Request, workspace_directory: &Path) -> HtmlResponse {
+    let workspace = match Workspace::open(workspace_directory) {
+        Ok(workspace) => workspace,
+        Err(message) => {
+            eprintln!("{}", message);
+            return html_response(500, html_page("Error", "  <p>Something went wrong.</p>\n"));
+        }
+    };
+
+    match (request.method(), request.url()) {
+        (Method::Get, "/") => html_response(200, sign_up_page(&workspace, None)),
+        (Method::Post, "/sign-up") => match handle_sign_up(request, workspace_directory) {
+            Ok(message) => {
+                let workspace = Workspace::open(workspace_directory).unwrap_or(workspace);
+                html_response(200, sign_up_page(&workspace, Some(("success", &message))))
+            }
+            Err(message) => html_response(400, sign_up_page(&workspace, Some(("error", &message)))),
+        },
+        _ => html_response(404, html_page("Not found", "  <p>Not found.</p>\n")),
+    }
+}
+
+/// Serves the sign-up form for the workspace until the process is stopped.
+pub fn serve(workspace_directory: &Path, address: &str) -> Result<(), String> {
+    let server = Server::http(address)
+        .map_err(|error| format!("Failed to listen on {}: {}", address, error))?;
+    eprintln!("Serving the sign-up form on http://{}/", address);
+
+    for mut request in server.incoming_requests() {
+        let response = handle_request(&mut request, workspace_directory);
+        if let Err(error) = request.respond(response) {
+            eprintln!("Failed to respond: {}", error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_form() -> Vec<(String, String)> {
+        form_urlencoded::parse(
+            b"name=Alice+Smith&discord_handle=alice&mailing_info=1+Alice+Lane&interests=cats\
+              &cannot_send_to_submitter=Bob+Jones&cannot_send_to_submitter=&cannot_send_to_submitter=Bob+Jones\
+              &cannot_receive_from_submitter=&random_string=xyz",
+        )
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect()
+    }
+
+    #[test]
+    fn test_submission_from_form() {
+        let submission = submission_from_form(&get_test_form()).unwrap();
+
+        assert_eq!(submission.name, "Alice Smith");
+        assert_eq!(submission.cannot_send_to_submitter, vec!["Bob Jones"]);
+        assert!(submission.cannot_receive_from_submitter.is_empty());
+        assert_eq!(submission.random_string, "xyz");
+    }
+
+    #[test]
+    fn test_submission_requires_name() {
+        let form: Vec<(String, String)> = get_test_form()
+            .into_iter()
+            .filter(|(key, _)| key != "name")
+            .collect();
+
+        assert!(submission_from_form(&form).is_err());
+    }
+
+    #[test]
+    fn test_submission_rejects_path_names() {
+        for name in ["../../etc/x", "a/b", "a\\b", "..", "a\0b", "a\nb"] {
+            let form: Vec<(String, String)> = get_test_form()
+                .into_iter()
+                .map(|(key, value)| {
+                    if key == "name" {
+                        (key, name.to_string())
+                    } else {
+                        (key, value)
+                    }
+                })
+                .collect();
+
+            assert!(submission_from_form(&form).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_deadline() {
+        assert_eq!(
+            parse_deadline("2024-12-01T18:00:00+01:00").unwrap(),
+            "2024-12-01T18:00:00+01:00"
+        );
+        assert!(parse_deadline("2024-12-01 18:00").is_ok());
+        assert!(parse_deadline("next friday").is_err());
+    }
+}
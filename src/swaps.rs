@@ -0,0 +1,266 @@
+//! Swap draws, where everyone is paired up with a partner and the two give each other a gift.
+//!
+//! Two people can be partners if the exclusions allow both directions. The pairs are a perfect matching in that
+//! undirected compatibility graph, found with Edmonds' blossom algorithm on a randomly ordered graph.
+//! With an odd number of participants, three of them form a triad instead (A sends to B, B to C and C to A).
+
+use std::{collections::HashSet, rc::Rc};
+
+use petgraph::{algo::matching::maximum_matching, graph::UnGraph};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    configuration::{Configuration, Participant},
+    permutation::{Assignment, Permutation},
+};
+
+/// How many triads are tried with an odd number of participants before giving up
+const MAX_TRIADS: usize = 1000;
+
+/// Two partners who give each other a gift
+type Pair = (Rc<Participant>, Rc<Participant>);
+
+/// Whether the two can swap gifts.
+fn can_swap(configuration: &Configuration, a: &Rc<Participant>, b: &Rc<Participant>) -> bool {
+    configuration.allows(a, b) && configuration.allows(b, a)
+}
+
+/// Pairs up all the participants at random, or returns the participants a maximum matching leaves without a partner.
+fn random_pairs<R: Rng>(
+    configuration: &Configuration,
+    participants: &[Rc<Participant>],
+    rng: &mut R,
+) -> Result<Vec<Pair>, Vec<Rc<Participant>>> {
+    // The blossom algorithm is deterministic, shuffling the nodes and edges is what makes the pairs random
+    let mut shuffled = participants.to_vec();
+    shuffled.shuffle(rng);
+    let mut graph = UnGraph::<Rc<Participant>, ()>::new_undirected();
+    let nodes: Vec<_> = shuffled
+        .iter()
+        .map(|participant| graph.add_node(participant.clone()))
+        .collect();
+    let mut edges = Vec::new();
+    for (i, a) in shuffled.iter().enumerate() {
+        for (j, b) in shuffled.iter().enumerate().skip(i + 1) {
+            if can_swap(configuration, a, b) {
+                edges.push((nodes[i], nodes[j]));
+            }
+        }
+    }
+    edges.shuffle(rng);
+    for (a, b) in edges {
+        graph.add_edge(a, b, ());
+    }
+
+    let matching = maximum_matching(&graph);
+    if matching.is_perfect() {
+        Ok(matching
+            .edges()
+            .map(|(a, b)| (graph[a].clone(), graph[b].clone()))
+            .collect())
+    } else {
+        let mut unmatched: Vec<Rc<Participant>> = nodes
+            .into_iter()
+            .filter(|&node| !matching.contains_node(node))
+            .map(|node| graph[node].clone())
+            .collect();
+        unmatched.sort_by(|a, b| a.name.cmp(&b.name));
+        Err(unmatched)
+    }
+}
+
+/// Every group of three that can give gifts around in a circle, in gift-giving order.
+fn triads(
+    configuration: &Configuration,
+    participants: &[Rc<Participant>],
+) -> Vec<[Rc<Participant>; 3]> {
+    let mut triads = Vec::new();
+    for (i, a) in participants.iter().enumerate() {
+        for (j, b) in participants.iter().enumerate().skip(i + 1) {
+            for c in participants.iter().skip(j + 1) {
+                if configuration.allows(a, b)
+                    && configuration.allows(b, c)
+                    && configuration.allows(c, a)
+                {
+                    triads.push([a.clone(), b.clone(), c.clone()]);
+                } else if configuration.allows(a, c)
+                    && configuration.allows(c, b)
+                    && configuration.allows(b, a)
+                {
+                    triads.push([a.clone(), c.clone(), b.clone()]);
+                }
+            }
+        }
+    }
+    triads
+}
+
+fn names(participants: &[Rc<Participant>]) -> String {
+    participants
+        .iter()
+        .map(|participant| participant.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Draws random swap pairs, plus one triad if the number of participants is odd.
+pub fn draw_swaps<R: Rng>(
+    configuration: &Configuration,
+    rng: &mut R,
+) -> Result<Permutation<Rc<Participant>>, String> {
+    let participants = configuration.sorted_participants();
+    if participants.len() < 2 {
+        return Err("Swaps need at least two participants".to_string());
+    }
+
+    let mut assignments = HashSet::new();
+    let pairs = if participants.len().is_multiple_of(2) {
+        random_pairs(configuration, &participants, rng).map_err(|unmatched| {
+            format!(
+                "No way to pair everyone up: {} can't all find a partner (partners have to be allowed to send to each other)",
+                names(&unmatched)
+            )
+        })?
+    } else {
+        let mut triads = triads(configuration, &participants);
+        triads.shuffle(rng);
+        let mut found = None;
+        for triad in triads.into_iter().take(MAX_TRIADS) {
+            let rest: Vec<Rc<Participant>> = participants
+                .iter()
+                .filter(|participant| !triad.contains(participant))
+                .cloned()
+                .collect();
+            if let Ok(pairs) = random_pairs(configuration, &rest, rng) {
+                found = Some((triad, pairs));
+                break;
+            }
+        }
+        let (triad, pairs) = found.ok_or_else(|| {
+            "No way to pair everyone up around a group of three. With an odd number of participants, three of them have to be able to send to each other in a circle and everyone else has to find a partner.".to_string()
+        })?;
+        for (index, sender) in triad.iter().enumerate() {
+            assignments.insert(Assignment {
+                sender: sender.clone(),
+                recipient: triad[(index + 1) % 3].clone(),
+            });
+        }
+        pairs
+    };
+    for (a, b) in pairs {
+        assignments.insert(Assignment {
+            sender: a.clone(),
+            recipient: b.clone(),
+        });
+        assignments.insert(Assignment {
+            sender: b,
+            recipient: a,
+        });
+    }
+
+    let permutation = Permutation::try_new(assignments, &configuration.participants)?;
+    configuration.ensure_valid_permutation(&permutation)?;
+    Ok(permutation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configuration::test_support, seed};
+
+    #[test]
+    fn test_draw_swaps() {
+        // Alice can't send to Bob, so they can't be partners
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "Dana"], &[("Alice", "Bob")]);
+        for seed in 0..10 {
+            let permutation =
+                draw_swaps(&configuration, &mut seed::rng_from_seed([seed; 32])).unwrap();
+            let cycles = permutation.cycles();
+            assert_eq!(cycles.len(), 2);
+            assert!(cycles.iter().all(|cycle| cycle.len() == 2));
+            assert!(!cycles
+                .iter()
+                .any(|cycle| cycle.iter().any(|p| p.name == "Alice")
+                    && cycle.iter().any(|p| p.name == "Bob")));
+        }
+    }
+
+    #[test]
+    fn test_odd_count_gets_one_triad() {
+        // Dana and Eve can only swap with each other, so Alice, Bob and Charlie have to form the triad
+        let mut exclusions = Vec::new();
+        for outsider in ["Dana", "Eve"] {
+            for insider in ["Alice", "Bob", "Charlie"] {
+                exclusions.push((outsider, insider));
+                exclusions.push((insider, outsider));
+            }
+        }
+        let configuration =
+            test_support::configuration(&["Alice", "Bob", "Charlie", "Dana", "Eve"], &exclusions);
+
+        for seed in 0..10 {
+            let permutation =
+                draw_swaps(&configuration, &mut seed::rng_from_seed([seed; 32])).unwrap();
+
+            let mut cycles: Vec<Vec<String>> = permutation
+                .cycles()
+                .into_iter()
+                .map(|cycle| {
+                    let mut names: Vec<String> = cycle
+                        .iter()
+                        .map(|participant| participant.name.clone())
+                        .collect();
+                    names.sort();
+                    names
+                })
+                .collect();
+            cycles.sort_by_key(Vec::len);
+            assert_eq!(
+                cycles,
+                vec![vec!["Dana", "Eve"], vec!["Alice", "Bob", "Charlie"]]
+            );
+        }
+    }
+
+    #[test]
+    fn test_infeasible_swaps() {
+        // Nobody can be Charlie's partner
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "Dana"],
+            &[
+                ("Charlie", "Alice"),
+                ("Charlie", "Bob"),
+                ("Dana", "Charlie"),
+            ],
+        );
+        let error = draw_swaps(&configuration, &mut seed::rng_from_seed([0; 32]))
+            .map(|_| ())
+            .unwrap_err();
+        assert!(error.contains("Charlie"));
+
+        // Dana can't send to anyone, so Dana fits neither in a triad nor in a pair
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "Dana", "Eve"],
+            &[
+                ("Dana", "Alice"),
+                ("Dana", "Bob"),
+                ("Dana", "Charlie"),
+                ("Dana", "Eve"),
+            ],
+        );
+        assert!(draw_swaps(&configuration, &mut seed::rng_from_seed([0; 32])).is_err());
+
+        // No group of three can send around in a circle
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie"],
+            &[("Alice", "Bob"), ("Alice", "Charlie")],
+        );
+        assert!(draw_swaps(&configuration, &mut seed::rng_from_seed([0; 32])).is_err());
+        assert!(draw_swaps(
+            &test_support::configuration(&["Alice"], &[]),
+            &mut seed::rng_from_seed([0; 32])
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Team draws for bigger-budget exchanges, where santa teams of two or three people jointly give one recipient a gift.
+//!
+//! Everyone is put on exactly one team. Every team gets a different recipient from outside the team, and every
+//! member has to be allowed to send to that recipient. The teams are a random split of the participants, and the
+//! recipients a random matching of teams to allowed recipients. If a split has no matching, another split is tried.
+
+use std::rc::Rc;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::configuration::{Configuration, Participant};
+
+/// How many random splits into teams are tried before giving up
+const ATTEMPTS: usize = 1000;
+
+#[derive(Debug)]
+pub struct Team {
+    /// Sorted by name
+    pub members: Vec<Rc<Participant>>,
+    pub recipient: Rc<Participant>,
+}
+
+/// Sizes of the teams for `count` participants, as many teams of `team_size` as possible and the rest of 2 or 3.
+fn team_sizes(count: usize, team_size: usize) -> Vec<usize> {
+    let mut sizes = vec![team_size; count / team_size];
+    match (team_size, count % team_size) {
+        (_, 0) => {}
+        // One team of three
+        (2, _) => *sizes.last_mut().unwrap() = 3,
+        // Two teams of two instead of a team of three and one person alone
+        (_, 1) => {
+            sizes.pop();
+            sizes.extend([2, 2]);
+        }
+        (_, remainder) => sizes.push(remainder),
+    }
+    sizes
+}
+
+/// Finds a recipient for every team with augmenting paths, where `candidates[team]` are the allowed recipients.
+fn match_recipients(candidates: &[Vec<usize>], participant_count: usize) -> Option<Vec<usize>> {
+    fn augment(
+        team: usize,
+        candidates: &[Vec<usize>],
+        team_of_recipient: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for &recipient in candidates[team].iter() {
+            if visited[recipient] {
+                continue;
+            }
+            visited[recipient] = true;
+            let free = match team_of_recipient[recipient] {
+                None => true,
+                Some(other) => augment(other, candidates, team_of_recipient, visited),
+            };
+            if free {
+                team_of_recipient[recipient] = Some(team);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut team_of_recipient = vec![None; participant_count];
+    for team in 0..candidates.len() {
+        if !augment(
+            team,
+            candidates,
+            &mut team_of_recipient,
+            &mut vec![false; participant_count],
+        ) {
+            return None;
+        }
+    }
+    let mut recipients = vec![0; candidates.len()];
+    for (recipient, team) in team_of_recipient.into_iter().enumerate() {
+        if let Some(team) = team {
+            recipients[team] = recipient;
+        }
+    }
+    Some(recipients)
+}
+
+/// Splits the participants into santa teams of about `team_size` (2 or 3) and gives every team a recipient.
+pub fn draw_teams<R: Rng>(
+    configuration: &Configuration,
+    team_size: usize,
+    rng: &mut R,
+) -> Result<Vec<Team>, String> {
+    if !(2..=3).contains(&team_size) {
+        return Err(format!("Teams have 2 or 3 members, not {}", team_size));
+    }
+    let participants = configuration.sorted_participants();
+    if participants.len() < 4 {
+        return Err(
+            "Teams need at least four participants, so that every team has someone outside it to give to"
+                .to_string(),
+        );
+    }
+    let sizes = team_sizes(participants.len(), team_size);
+
+    let mut order: Vec<usize> = (0..participants.len()).collect();
+    for _ in 0..ATTEMPTS {
+        order.shuffle(rng);
+        let mut teams: Vec<Vec<usize>> = Vec::new();
+        let mut rest = order.as_slice();
+        for &size in sizes.iter() {
+            let (team, remaining) = rest.split_at(size);
+            teams.push(team.to_vec());
+            rest = remaining;
+        }
+
+        let candidates: Vec<Vec<usize>> = teams
+            .iter()
+            .map(|team| {
+                let mut candidates: Vec<usize> = (0..participants.len())
+                    .filter(|recipient| {
+                        team.iter().all(|&member| {
+                            configuration.allows(&participants[member], &participants[*recipient])
+                        })
+                    })
+                    .collect();
+                candidates.shuffle(rng);
+                candidates
+            })
+            .collect();
+        if let Some(recipients) = match_recipients(&candidates, participants.len()) {
+            let mut drawn: Vec<Team> = teams
+                .into_iter()
+                .zip(recipients)
+                .map(|(mut team, recipient)| {
+                    team.sort();
+                    Team {
+                        members: team
+                            .into_iter()
+                            .map(|member| participants[member].clone())
+                            .collect(),
+                        recipient: participants[recipient].clone(),
+                    }
+                })
+                .collect();
+            drawn.sort_by(|a, b| a.members[0].name.cmp(&b.members[0].name));
+            return Ok(drawn);
+        }
+    }
+
+    Err(format!(
+        "Gave up after trying {} different teams without finding a recipient for every team. Every member of a team has to be allowed to send to the team's recipient, so the exclusions are probably too tight for teams.",
+        ATTEMPTS
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{configuration::test_support, seed};
+
+    #[test]
+    fn test_team_sizes() {
+        assert_eq!(team_sizes(6, 2), vec![2, 2, 2]);
+        assert_eq!(team_sizes(7, 2), vec![2, 2, 3]);
+        assert_eq!(team_sizes(7, 3), vec![3, 2, 2]);
+        assert_eq!(team_sizes(8, 3), vec![3, 3, 2]);
+    }
+
+    #[test]
+    fn test_draw_teams() {
+        // Nobody but Bob may send to Alice
+        let configuration = test_support::configuration(
+            &["Alice", "Bob", "Charlie", "Dana", "Eve", "Frank", "Grace"],
+            &[
+                ("Charlie", "Alice"),
+                ("Dana", "Alice"),
+                ("Eve", "Alice"),
+                ("Frank", "Alice"),
+                ("Grace", "Alice"),
+            ],
+        );
+
+        let teams = draw_teams(&configuration, 2, &mut seed::rng_from_seed([3; 32])).unwrap();
+
+        assert_eq!(teams.len(), 3);
+        let members: HashSet<_> = teams.iter().flat_map(|team| team.members.iter()).collect();
+        assert_eq!(members.len(), 7);
+        let recipients: HashSet<_> = teams.iter().map(|team| &team.recipient).collect();
+        assert_eq!(recipients.len(), 3);
+        for team in teams.iter() {
+            assert!((2..=3).contains(&team.members.len()));
+            assert!(!team.members.contains(&team.recipient));
+            assert!(team
+                .members
+                .iter()
+                .all(|member| configuration.allows(member, &team.recipient)));
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Draws valid assignments uniformly at random, so that no pair is likelier than any other.
+//!
+//! Randomized Ford-Fulkerson finds *a* valid assignment, but not every valid assignment equally often.
+//! For groups small enough to count exactly, this samples exactly uniformly: the number of ways to match
+//! the first k senders to every subset of k recipients is computed by dynamic programming, and the senders are then
+//! assigned from the last to the first, each recipient being picked with probability proportional to the number of ways
+//! to complete the assignment with it.
+//!
+//! Larger groups use a Markov chain instead, starting from the flow network's assignment and repeatedly swapping the
+//! recipients of two senders (or rotating those of three) whenever the result is still valid. Every move is as likely
+//! as its reverse, so the chain's stationary distribution is uniform over the valid assignments it can reach.
+//! With enough exclusions some valid assignments might not be reachable through such moves,
+//! so this is only guaranteed to be uniform when the chain is ergodic, which is usually but not always the case.
+
+use std::{collections::HashSet, rc::Rc};
+
+use rand::{seq::index, Rng};
+
+use crate::{
+    configuration::{Configuration, Participant},
+    counting::{self, EXACT_COUNT_LIMIT},
+    flow_network,
+    permutation::Assignment,
+};
+
+/// Markov chain steps per participant squared
+const STEPS_PER_PARTICIPANT_SQUARED: usize = 50;
+
+/// Samples a uniformly random perfect matching of a 0/1 matrix with at most `EXACT_COUNT_LIMIT` rows,
+/// returning the column of each row, or `None` if there isn't any.
+fn sample_exact<R: Rng + ?Sized>(matrix: &[Vec<bool>], rng: &mut R) -> Option<Vec<usize>> {
+    let n = matrix.len();
+
+    // ways[mask] is the number of ways to match the first popcount(mask) rows to exactly the columns in mask
+    let mut ways = vec![0u128; 1 << n];
+    ways[0] = 1;
+    for mask in 1usize..(1 << n) {
+        let row = mask.count_ones() as usize - 1;
+        ways[mask] = (0..n)
+            .filter(|&column| mask & (1 << column) != 0 && matrix[row][column])
+            .map(|column| ways[mask ^ (1 << column)])
+            .sum();
+    }
+
+    let mut mask = (1 << n) - 1;
+    if ways[mask] == 0 {
+        return None;
+    }
+    let mut columns = vec![0; n];
+    for row in (0..n).rev() {
+        let mut target = rng.gen_range(0..ways[mask]);
+        for column in 0..n {
+            if mask & (1 << column) == 0 || !matrix[row][column] {
+                continue;
+            }
+            let completions = ways[mask ^ (1 << column)];
+            if target < completions {
+                columns[row] = column;
+                mask ^= 1 << column;
+                break;
+            }
+            target -= completions;
+        }
+    }
+    Some(columns)
+}
+
+/// Runs the swap/rotation chain for `steps` steps from a valid assignment `columns` of the matrix.
+fn run_chain<R: Rng + ?Sized>(
+    matrix: &[Vec<bool>],
+    columns: &mut [usize],
+    steps: usize,
+    rng: &mut R,
+) {
+    let n = matrix.len();
+    if n < 2 {
+        return;
+    }
+    for _ in 0..steps {
+        let move_size = if n >= 3 && rng.gen_bool(0.5) { 3 } else { 2 };
+        // Rows in random order, so that both rotation directions are equally likely
+        let rows = index::sample(rng, n, move_size).into_vec();
+        let rotated: Vec<usize> = (0..move_size)
+            .map(|i| columns[rows[(i + 1) % move_size]])
+            .collect();
+        if rows
+            .iter()
+            .zip(rotated.iter())
+            .all(|(&row, &column)| matrix[row][column])
+        {
+            for (&row, &column) in rows.iter().zip(rotated.iter()) {
+                columns[row] = column;
+            }
+        }
+    }
+}
+
+/// Draws a valid assignment uniformly at random, exactly for groups of up to `EXACT_COUNT_LIMIT` participants
+/// and with the Markov chain for larger ones.
+pub fn sample_uniform_assignments<R: Rng>(
+    configuration: &Configuration,
+    rng: &mut R,
+) -> Result<HashSet<Assignment<Rc<Participant>>>, String> {
+    let participants = configuration.sorted_participants();
+    let matrix = counting::allowed_matrix(configuration);
+
+    let columns = if participants.len() <= EXACT_COUNT_LIMIT {
+        sample_exact(&matrix, rng).ok_or_else(|| {
+            // The flow network explains who makes an assignment impossible
+            flow_network::try_generate_assignments(configuration, false, rng)
+                .err()
+                .unwrap_or_else(|| "Failed to find a valid assignment".to_string())
+        })?
+    } else {
+        let start = flow_network::try_generate_assignments(configuration, false, rng)?;
+        let mut columns: Vec<usize> = participants
+            .iter()
+            .map(|sender| {
+                let recipient = &start
+                    .iter()
+                    .find(|assignment| &assignment.sender == sender)
+                    .unwrap()
+                    .recipient;
+                participants.iter().position(|p| p == recipient).unwrap()
+            })
+            .collect();
+        let steps = STEPS_PER_PARTICIPANT_SQUARED * participants.len() * participants.len();
+        run_chain(&matrix, &mut columns, steps, rng);
+        columns
+    };
+
+    Ok(columns
+        .into_iter()
+        .enumerate()
+        .map(|(row, column)| Assignment {
+            sender: participants[row].clone(),
+            recipient: participants[column].clone(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::counting::test_support::derangement_matrix;
+
+    #[test]
+    fn test_exact_sampling_is_uniform() {
+        // There are 9 derangements of 4 elements
+        let matrix = derangement_matrix(4);
+        let mut rng = rand::thread_rng();
+        let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+
+        for _ in 0..9000 {
+            let columns = sample_exact(&matrix, &mut rng).unwrap();
+            assert!(columns
+                .iter()
+                .enumerate()
+                .all(|(row, &column)| row != column));
+            *counts.entry(columns).or_default() += 1;
+        }
+
+        assert_eq!(counts.len(), 9);
+        assert!(counts.values().all(|&count| (800..1200).contains(&count)));
+    }
+
+    #[test]
+    fn test_exact_sampling_without_solution() {
+        let matrix = vec![vec![false, true], vec![false, true]];
+        assert!(sample_exact(&matrix, &mut rand::thread_rng()).is_none());
+    }
+
+    #[test]
+    fn test_chain_stays_valid_and_mixes() {
+        let matrix = derangement_matrix(4);
+        let mut rng = rand::thread_rng();
+        let mut columns = vec![1, 2, 3, 0];
+        let mut seen = HashSet::new();
+
+        for _ in 0..1000 {
+            run_chain(&matrix, &mut columns, 10, &mut rng);
+            assert!(columns
+                .iter()
+                .enumerate()
+                .all(|(row, &column)| row != column));
+            let mut sorted = columns.clone();
+            sorted.sort();
+            assert_eq!(sorted, vec![0, 1, 2, 3]);
+            seen.insert(columns.clone());
+        }
+
+        assert_eq!(seen.len(), 9);
+    }
+}
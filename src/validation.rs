@@ -0,0 +1,268 @@
+//! Checks sign-up responses for common mistakes before anything is drawn.
+//!
+//! Problems that make the responses unusable (duplicate names, exclusions that don't match anyone,
+//! no valid assignment at all) are errors. Everything else is a warning for the organizer to look at.
+
+use std::{collections::HashMap, fmt::Write};
+
+use serde::Serialize;
+
+use crate::{
+    configuration::Configuration,
+    counting::{self, AssignmentCount},
+    flow_network,
+    form_submission::{configuration_from_submissions, FormSubmission},
+};
+
+#[derive(Debug, Serialize)]
+pub struct UnresolvedName {
+    pub submitter: String,
+    pub column: &'static str,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EligibilityCount {
+    pub name: String,
+    pub eligible_recipients: usize,
+    pub eligible_senders: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub participant_count: usize,
+    /// Names that were submitted more than once. Only the last submission is used.
+    pub duplicates: Vec<String>,
+    pub unresolved_names: Vec<UnresolvedName>,
+    pub self_exclusions: Vec<String>,
+    pub empty_mailing_info: Vec<String>,
+    /// Participants with at most the threshold of people they can send to or receive from
+    pub few_eligible: Vec<EligibilityCount>,
+    pub feasible: bool,
+    pub infeasibility_reason: Option<String>,
+    /// How many valid assignments there are, exactly for small groups and estimated for larger ones
+    pub assignment_count: AssignmentCount,
+}
+
+impl ValidationReport {
+    pub fn has_errors(&self) -> bool {
+        !self.duplicates.is_empty() || !self.unresolved_names.is_empty() || !self.feasible
+    }
+
+    pub fn to_human(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "{} participants", self.participant_count).unwrap();
+
+        for name in self.duplicates.iter() {
+            writeln!(
+                report,
+                "ERROR: {:?} signed up more than once, only their last response is used",
+                name
+            )
+            .unwrap();
+        }
+        for unresolved in self.unresolved_names.iter() {
+            writeln!(
+                report,
+                "ERROR: {:?} listed {:?} under {:?}, but nobody by that name signed up",
+                unresolved.submitter, unresolved.name, unresolved.column
+            )
+            .unwrap();
+        }
+        if let Some(reason) = &self.infeasibility_reason {
+            writeln!(report, "ERROR: {}", reason).unwrap();
+        }
+        for name in self.self_exclusions.iter() {
+            writeln!(
+                report,
+                "WARNING: {:?} excluded themselves (harmless, nobody is their own Secret Santa anyway)",
+                name
+            )
+            .unwrap();
+        }
+        for name in self.empty_mailing_info.iter() {
+            writeln!(
+                report,
+                "WARNING: {:?} didn't fill in their mailing info",
+                name
+            )
+            .unwrap();
+        }
+        for count in self.few_eligible.iter() {
+            writeln!(
+                report,
+                "WARNING: {:?} can only send to {} and receive from {} people",
+                count.name, count.eligible_recipients, count.eligible_senders
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "{}", self.assignment_count).unwrap();
+
+        if self.has_errors() {
+            writeln!(report, "Validation failed.").unwrap();
+        } else {
+            writeln!(report, "A valid assignment exists.").unwrap();
+        }
+        report
+    }
+}
+
+/// How many people every participant can send to and receive from, sorted by name.
+pub fn eligibility_counts(configuration: &Configuration) -> Vec<EligibilityCount> {
+    let participants = configuration.sorted_participants();
+    participants
+        .iter()
+        .map(|participant| EligibilityCount {
+            name: participant.name.clone(),
+            eligible_recipients: participants
+                .iter()
+                .filter(|recipient| configuration.allows(participant, recipient))
+                .count(),
+            eligible_senders: participants
+                .iter()
+                .filter(|sender| configuration.allows(sender, participant))
+                .count(),
+        })
+        .collect()
+}
+
+/// Checks the submissions without performing a draw. Participants who can send to or receive from
+/// at most `few_eligible_threshold` people are reported.
+pub fn validate(submissions: &[FormSubmission], few_eligible_threshold: usize) -> ValidationReport {
+    let mut submission_counts: HashMap<&str, usize> = HashMap::new();
+    for submission in submissions {
+        *submission_counts.entry(&submission.name).or_default() += 1;
+    }
+    let mut duplicates: Vec<String> = submission_counts
+        .iter()
+        .filter(|(_, &count)| count > 1)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    duplicates.sort();
+
+    let mut unresolved_names = Vec::new();
+    let mut self_exclusions = Vec::new();
+    for submission in submissions {
+        for (column, names) in [
+            ("Sender Exclusions", &submission.cannot_send_to_submitter),
+            (
+                "Recipient Exclusions",
+                &submission.cannot_receive_from_submitter,
+            ),
+        ] {
+            // An empty answer is read as a single empty name
+            for name in names.iter().filter(|name| !name.is_empty()) {
+                if name == &submission.name {
+                    if !self_exclusions.contains(name) {
+                        self_exclusions.push(name.clone());
+                    }
+                } else if !submission_counts.contains_key(name.as_str()) {
+                    unresolved_names.push(UnresolvedName {
+                        submitter: submission.name.clone(),
+                        column,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let empty_mailing_info = submissions
+        .iter()
+        .filter(|submission| submission.mailing_info.trim().is_empty())
+        .map(|submission| submission.name.clone())
+        .collect();
+
+    let configuration = configuration_from_submissions(submissions);
+    let few_eligible = eligibility_counts(&configuration)
+        .into_iter()
+        .filter(|count| {
+            count.eligible_recipients <= few_eligible_threshold
+                || count.eligible_senders <= few_eligible_threshold
+        })
+        .collect();
+
+    let infeasibility_reason =
+        flow_network::try_generate_assignments(&configuration, false, &mut rand::thread_rng())
+            .err();
+
+    let assignment_count = counting::count_assignments(
+        &configuration,
+        counting::DEFAULT_SAMPLES,
+        &mut rand::thread_rng(),
+    );
+
+    ValidationReport {
+        participant_count: configuration.participants.len(),
+        duplicates,
+        unresolved_names,
+        self_exclusions,
+        empty_mailing_info,
+        few_eligible,
+        feasible: infeasibility_reason.is_none(),
+        infeasibility_reason,
+        assignment_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_submission(name: &str, cannot_send_to_submitter: &[&str]) -> FormSubmission {
+        FormSubmission {
+            timestamp: "12/8/2023 14:14:28".to_string(),
+            name: name.to_string(),
+            discord_handle: name.to_lowercase(),
+            cannot_send_to_submitter: cannot_send_to_submitter
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            cannot_receive_from_submitter: vec!["".to_string()],
+            mailing_info: format!("1 {} Lane", name),
+            interests: "Programming".to_string(),
+            anything_else: "".to_string(),
+            random_string: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_problems() {
+        let submissions = vec![
+            get_test_submission("Alice", &["Alice", "Zoe"]),
+            get_test_submission("Bob", &[""]),
+            get_test_submission("Charlie", &[""]),
+            get_test_submission("Charlie", &[""]),
+        ];
+
+        let report = validate(&submissions, 1);
+
+        assert_eq!(report.participant_count, 3);
+        assert_eq!(report.duplicates, vec!["Charlie"]);
+        assert_eq!(report.self_exclusions, vec!["Alice"]);
+        assert_eq!(report.unresolved_names.len(), 1);
+        assert_eq!(report.unresolved_names[0].name, "Zoe");
+        assert!(report.few_eligible.is_empty());
+        assert!(report.feasible);
+        assert_eq!(report.assignment_count, AssignmentCount::Exact { count: 2 });
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn test_validate_reports_infeasibility() {
+        let submissions = vec![
+            get_test_submission("Alice", &["Bob", "Charlie"]),
+            get_test_submission("Bob", &[""]),
+            get_test_submission("Charlie", &[""]),
+        ];
+
+        let report = validate(&submissions, 0);
+
+        assert!(!report.feasible);
+        assert_eq!(report.assignment_count, AssignmentCount::Exact { count: 0 });
+        assert_eq!(report.few_eligible.len(), 1);
+        assert_eq!(report.few_eligible[0].name, "Alice");
+        assert_eq!(report.few_eligible[0].eligible_senders, 0);
+    }
+}
@@ -0,0 +1,274 @@
+//! Renders a draw as rings of names, one ring per cycle of gift-giving, for showing at the reveal party.
+//!
+//! Every ring is drawn clockwise: each person gives a gift to the next person on their ring.
+
+use std::{collections::HashMap, f64::consts::PI, fmt::Write};
+
+use clap::ValueEnum;
+
+use crate::server::escape_html;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum VisualizationFormat {
+    Svg,
+    Html,
+}
+
+impl VisualizationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VisualizationFormat::Svg => "svg",
+            VisualizationFormat::Html => "html",
+        }
+    }
+}
+
+pub struct RingOptions {
+    /// Only show first names, adding the initial of the surname where first names are shared
+    pub hide_surnames: bool,
+    /// Draw the longest cycle(s) in a different color
+    pub highlight_longest: bool,
+}
+
+const MAX_WIDTH: f64 = 1200.0;
+const LABEL_MARGIN: f64 = 90.0;
+const NODE_RADIUS: f64 = 6.0;
+const COLOR: &str = "#2e7d32";
+const HIGHLIGHT_COLOR: &str = "#c62828";
+
+/// The names to show for every full name.
+fn display_names(cycles: &[Vec<String>], hide_surnames: bool) -> HashMap<String, String> {
+    let names: Vec<&String> = cycles.iter().flatten().collect();
+    if !hide_surnames {
+        return names
+            .into_iter()
+            .map(|name| (name.clone(), name.clone()))
+            .collect();
+    }
+
+    let first_name = |name: &str| name.split_whitespace().next().unwrap_or(name).to_string();
+    let mut first_name_counts: HashMap<String, usize> = HashMap::new();
+    for name in names.iter() {
+        *first_name_counts.entry(first_name(name)).or_default() += 1;
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let first = first_name(name);
+            let surname_initial = name
+                .split_whitespace()
+                .nth(1)
+                .and_then(|surname| surname.chars().next());
+            let shown = match surname_initial {
+                Some(initial) if first_name_counts[&first] > 1 => format!("{} {}.", first, initial),
+                _ => first,
+            };
+            (name.clone(), shown)
+        })
+        .collect()
+}
+
+/// Orders the cycles for display: longest first, each starting with its alphabetically first name.
+pub fn arrange_cycles(mut cycles: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    for cycle in cycles.iter_mut() {
+        if let Some(first) = (0..cycle.len()).min_by_key(|&index| &cycle[index]) {
+            cycle.rotate_left(first);
+        }
+    }
+    cycles.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+    cycles
+}
+
+/// Renders the cycles, which should be arranged with `arrange_cycles`, as an SVG image.
+pub fn render_svg(cycles: &[Vec<String>], options: &RingOptions) -> String {
+    let names = display_names(cycles, options.hide_surnames);
+    let longest = cycles.iter().map(Vec::len).max().unwrap_or(0);
+
+    // Lay the rings out left to right, wrapping into rows
+    let mut rings = Vec::new();
+    let (mut x, mut y, mut row_height, mut width) = (0.0, 0.0, 0.0f64, 0.0f64);
+    for cycle in cycles {
+        let radius = f64::max(50.0, cycle.len() as f64 * 16.0);
+        let cell = 2.0 * (radius + LABEL_MARGIN);
+        if x > 0.0 && x + cell > MAX_WIDTH {
+            x = 0.0;
+            y += row_height;
+            row_height = 0.0;
+        }
+        rings.push((cycle, x + cell / 2.0, y + cell / 2.0, radius));
+        x += cell;
+        width = width.max(x);
+        row_height = row_height.max(cell);
+    }
+    let height = y + row_height;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" font-size="14">"#,
+        width.ceil(),
+        height.ceil()
+    )
+    .unwrap();
+    writeln!(svg, "  <defs>").unwrap();
+    for (id, color) in [("arrow", COLOR), ("arrow-highlight", HIGHLIGHT_COLOR)] {
+        writeln!(
+            svg,
+            r#"    <marker id="{}" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="{}"/></marker>"#,
+            id, color
+        )
+        .unwrap();
+    }
+    writeln!(svg, "  </defs>").unwrap();
+
+    for (cycle, center_x, center_y, radius) in rings {
+        let highlighted = options.highlight_longest && cycle.len() == longest;
+        let (color, marker, stroke_width) = if highlighted {
+            (HIGHLIGHT_COLOR, "arrow-highlight", 3)
+        } else {
+            (COLOR, "arrow", 2)
+        };
+        let point = |index: usize| {
+            // Start at the top and go clockwise
+            let angle = 2.0 * PI * index as f64 / cycle.len() as f64 - PI / 2.0;
+            (
+                angle,
+                center_x + radius * angle.cos(),
+                center_y + radius * angle.sin(),
+            )
+        };
+
+        writeln!(svg, "  <g>").unwrap();
+        for index in 0..cycle.len() {
+            // Shorten the arc a little at both ends so that the arrow head doesn't hide the node
+            let (start_angle, _, _) = point(index);
+            let (end_angle, _, _) = point(index + 1);
+            let gap = (NODE_RADIUS + 2.0) / radius;
+            let (from, to) = (start_angle + gap, end_angle - gap);
+            writeln!(
+                svg,
+                r#"    <path d="M {:.1} {:.1} A {:.1} {:.1} 0 0 1 {:.1} {:.1}" fill="none" stroke="{}" stroke-width="{}" marker-end="url(#{})"/>"#,
+                center_x + radius * from.cos(),
+                center_y + radius * from.sin(),
+                radius,
+                radius,
+                center_x + radius * to.cos(),
+                center_y + radius * to.sin(),
+                color,
+                stroke_width,
+                marker
+            )
+            .unwrap();
+        }
+        for (index, name) in cycle.iter().enumerate() {
+            let (angle, node_x, node_y) = point(index);
+            let label_x = center_x + (radius + 14.0) * angle.cos();
+            let label_y = center_y + (radius + 14.0) * angle.sin();
+            let anchor = if angle.cos() > 0.3 {
+                "start"
+            } else if angle.cos() < -0.3 {
+                "end"
+            } else {
+                "middle"
+            };
+            writeln!(
+                svg,
+                r#"    <circle cx="{:.1}" cy="{:.1}" r="{}" fill="{}"/>"#,
+                node_x, node_y, NODE_RADIUS, color
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"    <text x="{:.1}" y="{:.1}" text-anchor="{}" dominant-baseline="middle">{}</text>"#,
+                label_x,
+                label_y,
+                anchor,
+                escape_html(&names[name])
+            )
+            .unwrap();
+        }
+        writeln!(svg, "  </g>").unwrap();
+    }
+    writeln!(svg, "</svg>").unwrap();
+    svg
+}
+
+/// Renders the cycles as a standalone HTML page with the SVG image and a short summary.
+pub fn render_html(cycles: &[Vec<String>], options: &RingOptions, title: &str) -> String {
+    let participant_count: usize = cycles.iter().map(Vec::len).sum();
+    let longest = cycles.iter().map(Vec::len).max().unwrap_or(0);
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, r#"<html lang="en">"#).unwrap();
+    writeln!(html, "<head>").unwrap();
+    writeln!(html, r#"  <meta charset="utf-8">"#).unwrap();
+    writeln!(html, "  <title>{}</title>", escape_html(title)).unwrap();
+    writeln!(
+        html,
+        "  <style>body {{ font-family: sans-serif; text-align: center; }} svg {{ max-width: 100%; height: auto; }}</style>"
+    )
+    .unwrap();
+    writeln!(html, "</head>").unwrap();
+    writeln!(html, "<body>").unwrap();
+    writeln!(html, "  <h1>{}</h1>", escape_html(title)).unwrap();
+    writeln!(
+        html,
+        "  <p>{} people in {} {}, the longest chain has {} people. Everyone gave a gift to the next person clockwise.</p>",
+        participant_count,
+        cycles.len(),
+        if cycles.len() == 1 { "ring" } else { "rings" },
+        longest
+    )
+    .unwrap();
+    html.push_str(&render_svg(cycles, options));
+    writeln!(html, "</body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_cycles() -> Vec<Vec<String>> {
+        arrange_cycles(vec![
+            vec!["Bob Jones".to_string(), "Alice Smith".to_string()],
+            vec![
+                "Eve Adams".to_string(),
+                "Charlie Brown".to_string(),
+                "Alice Jones".to_string(),
+            ],
+        ])
+    }
+
+    #[test]
+    fn test_arrange_cycles() {
+        let cycles = get_test_cycles();
+
+        assert_eq!(cycles[0], vec!["Alice Jones", "Eve Adams", "Charlie Brown"]);
+        assert_eq!(cycles[1], vec!["Alice Smith", "Bob Jones"]);
+    }
+
+    #[test]
+    fn test_hide_surnames() {
+        let names = display_names(&get_test_cycles(), true);
+
+        assert_eq!(names["Alice Smith"], "Alice S.");
+        assert_eq!(names["Alice Jones"], "Alice J.");
+        assert_eq!(names["Bob Jones"], "Bob");
+
+        let svg = render_svg(
+            &get_test_cycles(),
+            &RingOptions {
+                hide_surnames: true,
+                highlight_longest: true,
+            },
+        );
+        assert!(!svg.contains("Smith"));
+        assert!(svg.contains(HIGHLIGHT_COLOR));
+        // One arrow per assignment
+        assert_eq!(svg.matches("marker-end=").count(), 5);
+    }
+}
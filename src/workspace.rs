@@ -0,0 +1,679 @@
+//! A persistent event workspace: a directory holding the roster, every draw, delivery status and history,
+//! so that later commands (repair, insert, verify, reveal, ...) operate on saved state instead of regenerated randomness.
+//!
+//! Everything is stored in a single human-readable `event.json` file inside the workspace directory.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    commitment::{Recipe, Reveal},
+    configuration::Configuration,
+    form_submission::{
+        configuration_from_submissions, seed_derivation_from_submissions, FormSubmission,
+    },
+    graph_export,
+    mailbox::Message,
+    seed::SeedDerivation,
+};
+
+const STATE_FILE_NAME: &str = "event.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventConfig {
+    pub name: String,
+    /// RFC 3339 time after which the sign-up server stops accepting sign-ups
+    #[serde(default)]
+    pub sign_up_deadline: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DrawKind {
+    Draw,
+    Repair,
+    Insert,
+    /// One of several rounds drawn together
+    Round,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum DeliveryStatus {
+    Pending,
+    Written { file_path: String, at: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrawRecord {
+    pub id: usize,
+    pub kind: DrawKind,
+    /// The draw this one was derived from, for repairs and insertions
+    pub parent: Option<usize>,
+    pub created_at: String,
+    pub method: Option<String>,
+    pub commitment: String,
+    pub reveal: Reveal,
+    /// How the seed was derived from the participants' random strings, if it was
+    pub seed_derivation: Option<String>,
+    /// Keyed by sender name
+    pub deliveries: BTreeMap<String, DeliveryStatus>,
+    /// When each sender first looked at their assignment in the participant portal, keyed by sender name
+    #[serde(default)]
+    pub portal_views: BTreeMap<String, String>,
+}
+
+impl DrawRecord {
+    /// The recipient of the given sender in this draw.
+    pub fn recipient_of(&self, sender: &str) -> Option<&str> {
+        self.reveal
+            .assignments
+            .iter()
+            .find(|assignment| assignment.sender == sender)
+            .map(|assignment| assignment.recipient.as_str())
+    }
+
+    /// The santa of the given recipient in this draw.
+    pub fn santa_of(&self, recipient: &str) -> Option<&str> {
+        self.reveal
+            .assignments
+            .iter()
+            .find(|assignment| assignment.recipient == recipient)
+            .map(|assignment| assignment.sender.as_str())
+    }
+
+    pub fn participant_names(&self) -> HashSet<String> {
+        self.reveal
+            .assignments
+            .iter()
+            .map(|assignment| assignment.sender.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub at: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventState {
+    pub config: EventConfig,
+    pub submissions: Vec<FormSubmission>,
+    /// Participants who dropped out. Their submissions are kept so that old draws can still be verified.
+    #[serde(default)]
+    pub withdrawn: Vec<String>,
+    #[serde(default)]
+    pub draws: Vec<DrawRecord>,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    /// Secret tokens for the participant portal links, keyed by participant name
+    #[serde(default)]
+    pub portal_tokens: BTreeMap<String, String>,
+    /// Anonymous messages between santas and their recipients
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    /// Secret salt for the anonymous IDs in graph exports, so that they stay the same across exports
+    #[serde(default)]
+    pub anonymization_salt: Option<String>,
+}
+
+pub struct Workspace {
+    pub directory: PathBuf,
+    pub state: EventState,
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+impl Workspace {
+    /// Creates a new workspace in `directory`, refusing to overwrite an existing one.
+    pub fn init(
+        directory: &Path,
+        name: String,
+        submissions: Vec<FormSubmission>,
+    ) -> Result<Workspace, String> {
+        if directory.join(STATE_FILE_NAME).exists() {
+            return Err(format!(
+                "{} already contains an event workspace",
+                directory.display()
+            ));
+        }
+        fs::create_dir_all(directory)
+            .map_err(|error| format!("Failed to create {}: {}", directory.display(), error))?;
+
+        let mut workspace = Workspace {
+            directory: directory.to_path_buf(),
+            state: EventState {
+                config: EventConfig {
+                    name,
+                    sign_up_deadline: None,
+                },
+                submissions,
+                withdrawn: Vec::new(),
+                draws: Vec::new(),
+                history: Vec::new(),
+                portal_tokens: BTreeMap::new(),
+                messages: Vec::new(),
+                anonymization_salt: None,
+            },
+        };
+        workspace.log(format!(
+            "Created the workspace with {} sign-ups",
+            workspace.state.submissions.len()
+        ));
+        workspace.save()?;
+        Ok(workspace)
+    }
+
+    pub fn open(directory: &Path) -> Result<Workspace, String> {
+        let state_path = directory.join(STATE_FILE_NAME);
+        let contents = fs::read_to_string(&state_path)
+            .map_err(|error| format!("Failed to read {}: {}", state_path.display(), error))?;
+        let state = serde_json::from_str(&contents)
+            .map_err(|error| format!("Failed to parse {}: {}", state_path.display(), error))?;
+        Ok(Workspace {
+            directory: directory.to_path_buf(),
+            state,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        // Write to a temporary file first so that a crash never leaves a half-written state behind
+        let state_path = self.directory.join(STATE_FILE_NAME);
+        let temporary_path = self.directory.join(format!("{}.tmp", STATE_FILE_NAME));
+        fs::write(
+            &temporary_path,
+            serde_json::to_string_pretty(&self.state).unwrap(),
+        )
+        .and_then(|_| fs::rename(&temporary_path, &state_path))
+        .map_err(|error| format!("Failed to write {}: {}", state_path.display(), error))
+    }
+
+    pub fn log(&mut self, message: impl Into<String>) {
+        self.state.history.push(HistoryEntry {
+            at: now(),
+            message: message.into(),
+        });
+    }
+
+    pub fn matchings_directory(&self) -> String {
+        self.directory.join("matchings").display().to_string()
+    }
+
+    /// Adds every submission whose name isn't on the roster yet, returning the names that were added.
+    pub fn import_submissions(&mut self, submissions: Vec<FormSubmission>) -> Vec<String> {
+        let mut added = Vec::new();
+        for submission in submissions {
+            if self
+                .state
+                .submissions
+                .iter()
+                .any(|existing| existing.name == submission.name)
+            {
+                continue;
+            }
+            added.push(submission.name.clone());
+            self.state.submissions.push(submission);
+        }
+        added
+    }
+
+    /// Submissions of everyone who hasn't withdrawn.
+    pub fn active_submissions(&self) -> Vec<FormSubmission> {
+        self.state
+            .submissions
+            .iter()
+            .filter(|submission| !self.state.withdrawn.contains(&submission.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Submissions of exactly the people who took part in the given draw.
+    pub fn submissions_for_draw(&self, draw: &DrawRecord) -> Vec<FormSubmission> {
+        let names = draw.participant_names();
+        self.state
+            .submissions
+            .iter()
+            .filter(|submission| names.contains(&submission.name))
+            .cloned()
+            .collect()
+    }
+
+    pub fn configuration_for_draw(&self, draw: &DrawRecord) -> Configuration {
+        configuration_from_submissions(&self.submissions_for_draw(draw))
+    }
+
+    /// The random strings the seed of the given draw has to be derived from, whatever seed the organizer passed.
+    /// Repairs and insertions have none, since their seed is derived from the previous draw.
+    pub fn seed_derivation_for_draw(&self, draw: &DrawRecord) -> Option<SeedDerivation> {
+        match draw.reveal.recipe {
+            Recipe::Draw { .. } | Recipe::Round { .. } => Some(seed_derivation_from_submissions(
+                &self.submissions_for_draw(draw),
+            )),
+            Recipe::Repair { .. } | Recipe::Insert { .. } => None,
+        }
+    }
+
+    /// The draw with the given id, or the most recent one.
+    pub fn draw(&self, id: Option<usize>) -> Result<&DrawRecord, String> {
+        match id {
+            Some(id) => self
+                .state
+                .draws
+                .iter()
+                .find(|draw| draw.id == id)
+                .ok_or_else(|| format!("There is no draw #{} in this workspace", id)),
+            None => self
+                .state
+                .draws
+                .last()
+                .ok_or_else(|| "This workspace doesn't contain any draws yet".to_string()),
+        }
+    }
+
+    /// Stores a new draw and returns its id. Senders who keep the same recipient as in the
+    /// parent draw keep their delivery status, everyone else starts out as pending.
+    pub fn record_draw(
+        &mut self,
+        kind: DrawKind,
+        parent: Option<usize>,
+        method: Option<String>,
+        reveal: Reveal,
+        seed_derivation: Option<String>,
+    ) -> usize {
+        let id = self.state.draws.last().map_or(1, |draw| draw.id + 1);
+        let parent_draw = parent.and_then(|parent| self.draw(Some(parent)).ok());
+
+        let deliveries = reveal
+            .assignments
+            .iter()
+            .map(|assignment| {
+                let carried_over = parent_draw.and_then(|parent_draw| {
+                    parent_draw
+                        .reveal
+                        .assignments
+                        .iter()
+                        .any(|previous| {
+                            previous.sender == assignment.sender
+                                && previous.recipient == assignment.recipient
+                        })
+                        .then(|| parent_draw.deliveries.get(&assignment.sender).cloned())
+                        .flatten()
+                });
+                (
+                    assignment.sender.clone(),
+                    carried_over.unwrap_or(DeliveryStatus::Pending),
+                )
+            })
+            .collect();
+
+        self.state.draws.push(DrawRecord {
+            id,
+            kind,
+            parent,
+            created_at: now(),
+            method,
+            commitment: reveal.commitment(),
+            reveal,
+            seed_derivation,
+            deliveries,
+            portal_views: BTreeMap::new(),
+        });
+        id
+    }
+
+    /// The participant's portal token, issuing a new one if they don't have one yet.
+    pub fn portal_token(&mut self, name: &str) -> String {
+        self.state
+            .portal_tokens
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let mut token = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut token);
+                hex::encode(token)
+            })
+            .clone()
+    }
+
+    /// The salt for anonymous IDs in graph exports, creating it the first time it's needed.
+    pub fn anonymization_salt(&mut self) -> String {
+        self.state
+            .anonymization_salt
+            .get_or_insert_with(graph_export::random_salt)
+            .clone()
+    }
+
+    /// The participant the portal token was issued to. The SHA-256 digests of the tokens are compared, and always
+    /// with every issued token, so how long the lookup takes doesn't tell how much of a guessed token was right.
+    pub fn participant_for_token(&self, token: &str) -> Option<&str> {
+        let digest = Sha256::digest(token.as_bytes());
+        let mut participant = None;
+        for (name, issued) in self.state.portal_tokens.iter() {
+            let difference = Sha256::digest(issued.as_bytes())
+                .iter()
+                .zip(digest.iter())
+                .fold(0, |difference, (a, b)| difference | (a ^ b));
+            if difference == 0 {
+                participant = Some(name.as_str());
+            }
+        }
+        participant
+    }
+
+    /// Records that the sender looked at their assignment in the portal, keeping the time of the first view.
+    /// Returns whether this was the first view.
+    pub fn mark_viewed(&mut self, draw_id: usize, sender: &str) -> bool {
+        let Some(draw) = self.state.draws.iter_mut().find(|draw| draw.id == draw_id) else {
+            return false;
+        };
+        if draw.portal_views.contains_key(sender) {
+            return false;
+        }
+        draw.portal_views.insert(sender.to_string(), now());
+        true
+    }
+
+    pub fn mark_written(&mut self, draw_id: usize, sender: &str, file_path: &str) {
+        if let Some(draw) = self.state.draws.iter_mut().find(|draw| draw.id == draw_id) {
+            draw.deliveries.insert(
+                sender.to_string(),
+                DeliveryStatus::Written {
+                    file_path: file_path.to_string(),
+                    at: now(),
+                },
+            );
+        }
+    }
+}
+
+/// Fixtures for the tests of every module that works on workspaces.
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+
+    /// A fresh directory for the test that doesn't exist yet.
+    pub fn temporary_directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("secret_santa_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    pub fn submission(name: &str) -> FormSubmission {
+        FormSubmission {
+            timestamp: String::new(),
+            name: name.to_string(),
+            discord_handle: format!("{}#0000", name.to_lowercase()),
+            cannot_send_to_submitter: Vec::new(),
+            cannot_receive_from_submitter: Vec::new(),
+            mailing_info: format!("1 {} Lane", name),
+            interests: String::new(),
+            anything_else: String::new(),
+            random_string: String::new(),
+        }
+    }
+
+    /// A new workspace called "Test event" in a temporary directory, with a sign-up for each name.
+    pub fn workspace(test: &str, names: &[&str]) -> Workspace {
+        Workspace::init(
+            &temporary_directory(test),
+            "Test event".to_string(),
+            names.iter().map(|name| submission(name)).collect(),
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{test_support::submission, *};
+    use crate::{
+        configuration::{test_support, Participant},
+        permutation::{Assignment, Permutation},
+    };
+
+    const NAMES: [&str; 4] = ["Alice", "Bob", "Charlie", "David"];
+
+    fn workspace(test: &str) -> Workspace {
+        super::test_support::workspace(test, &NAMES)
+    }
+
+    /// A reveal where each (sender, recipient) pair is given by name.
+    fn reveal(pairs: &[(&str, &str)]) -> Reveal {
+        let configuration = test_support::configuration(&NAMES, &[]);
+        let find = |name: &str| -> Rc<Participant> {
+            configuration
+                .participants
+                .iter()
+                .find(|participant| participant.name == name)
+                .unwrap()
+                .clone()
+        };
+        let assignments = pairs
+            .iter()
+            .map(|(sender, recipient)| Assignment {
+                sender: find(sender),
+                recipient: find(recipient),
+            })
+            .collect();
+        let permutation = Permutation::try_new(assignments, &configuration.participants).unwrap();
+        Reveal::new(
+            &permutation,
+            [0; 32],
+            Recipe::Draw {
+                method: "flow-network".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_init_refuses_existing_workspace() {
+        let workspace = workspace("init");
+
+        let error = Workspace::init(&workspace.directory, "Again".to_string(), Vec::new())
+            .map(|_| ())
+            .unwrap_err();
+        assert!(error.contains("already contains an event workspace"));
+        // The first workspace is untouched
+        assert_eq!(
+            Workspace::open(&workspace.directory)
+                .unwrap()
+                .state
+                .submissions
+                .len(),
+            4
+        );
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_open() {
+        let mut workspace = workspace("save");
+        workspace.state.withdrawn.push("Bob".to_string());
+        let id = workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            Some("flow-network".to_string()),
+            reveal(&[
+                ("Alice", "Bob"),
+                ("Bob", "Charlie"),
+                ("Charlie", "David"),
+                ("David", "Alice"),
+            ]),
+            None,
+        );
+        workspace.save().unwrap();
+
+        let opened = Workspace::open(&workspace.directory).unwrap();
+        assert_eq!(opened.state.config.name, "Test event");
+        assert_eq!(opened.state.withdrawn, vec!["Bob"]);
+        let draw = opened.draw(Some(id)).unwrap();
+        assert_eq!(draw.reveal, workspace.draw(Some(id)).unwrap().reveal);
+        assert_eq!(draw.commitment, draw.reveal.commitment());
+        assert_eq!(draw.method.as_deref(), Some("flow-network"));
+        assert_eq!(opened.state.history.len(), workspace.state.history.len());
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_roster() {
+        let mut workspace = workspace("roster");
+
+        let added = workspace.import_submissions(vec![
+            submission("Bob"),
+            submission("Eve"),
+            submission("Eve"),
+        ]);
+        assert_eq!(added, vec!["Eve"]);
+        assert_eq!(workspace.state.submissions.len(), 5);
+
+        workspace.state.withdrawn.push("Charlie".to_string());
+        let active: Vec<String> = workspace
+            .active_submissions()
+            .into_iter()
+            .map(|submission| submission.name)
+            .collect();
+        assert_eq!(active, vec!["Alice", "Bob", "David", "Eve"]);
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_draw_lookup() {
+        let mut workspace = workspace("lookup");
+        assert!(workspace.draw(None).is_err());
+        assert!(workspace.draw(Some(1)).is_err());
+
+        let cycle = reveal(&[
+            ("Alice", "Bob"),
+            ("Bob", "Charlie"),
+            ("Charlie", "David"),
+            ("David", "Alice"),
+        ]);
+        let first = workspace.record_draw(DrawKind::Draw, None, None, cycle.clone(), None);
+        let second = workspace.record_draw(DrawKind::Draw, None, None, cycle, None);
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(workspace.draw(None).unwrap().id, 2);
+        assert_eq!(workspace.draw(Some(1)).unwrap().id, 1);
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_hand_picked_seed_fails_the_seed_check() {
+        let mut workspace = workspace("hand_picked_seed");
+        for submission in workspace.state.submissions.iter_mut() {
+            submission.random_string = format!("{} was here", submission.name);
+        }
+
+        // Drawn with --seed, so no seed derivation was stored with the draw
+        let mut cycle = reveal(&[
+            ("Alice", "Bob"),
+            ("Bob", "Charlie"),
+            ("Charlie", "David"),
+            ("David", "Alice"),
+        ]);
+        let id = workspace.record_draw(DrawKind::Draw, None, None, cycle.clone(), None);
+        let draw = workspace.draw(Some(id)).unwrap();
+        let seed_derivation = workspace.seed_derivation_for_draw(draw).unwrap();
+        assert!(seed_derivation
+            .ensure_derived_seed(&draw.reveal.seed)
+            .is_err());
+
+        cycle.seed = hex::encode(seed_derivation.seed);
+        let id = workspace.record_draw(DrawKind::Draw, None, None, cycle, None);
+        let draw = workspace.draw(Some(id)).unwrap();
+        assert!(seed_derivation
+            .ensure_derived_seed(&draw.reveal.seed)
+            .is_ok());
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_participant_for_token() {
+        let mut workspace = workspace("tokens");
+        let alice = "0a1b2c3d4e5f60718293a4b5c6d7e8f9".to_string();
+        workspace
+            .state
+            .portal_tokens
+            .insert("Alice".to_string(), alice.clone());
+        assert_eq!(workspace.portal_token("Alice"), alice);
+        let bob = workspace.portal_token("Bob");
+        assert_eq!(bob.len(), 32);
+        assert_ne!(bob, alice);
+
+        assert_eq!(workspace.participant_for_token(&alice), Some("Alice"));
+        assert_eq!(workspace.participant_for_token(&bob), Some("Bob"));
+        for guess in [
+            String::new(),
+            alice[..alice.len() - 1].to_string(),
+            format!("{}0", alice),
+            alice.to_uppercase(),
+        ] {
+            assert_eq!(workspace.participant_for_token(&guess), None);
+        }
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+
+    #[test]
+    fn test_deliveries_carry_over_for_unchanged_pairs() {
+        let mut workspace = workspace("deliveries");
+        let first = workspace.record_draw(
+            DrawKind::Draw,
+            None,
+            None,
+            reveal(&[
+                ("Alice", "Bob"),
+                ("Bob", "Charlie"),
+                ("Charlie", "David"),
+                ("David", "Alice"),
+            ]),
+            None,
+        );
+        workspace.mark_written(first, "Alice", "alice.txt");
+        workspace.mark_written(first, "Bob", "bob.txt");
+
+        // Alice keeps Bob, Bob gets someone else
+        let second_reveal = reveal(&[
+            ("Alice", "Bob"),
+            ("Bob", "David"),
+            ("David", "Charlie"),
+            ("Charlie", "Alice"),
+        ]);
+        let second = workspace.record_draw(
+            DrawKind::Repair,
+            Some(first),
+            None,
+            second_reveal.clone(),
+            None,
+        );
+        let deliveries = &workspace.draw(Some(second)).unwrap().deliveries;
+        assert!(matches!(
+            &deliveries["Alice"],
+            DeliveryStatus::Written { file_path, .. } if file_path == "alice.txt"
+        ));
+        for sender in ["Bob", "Charlie", "David"] {
+            assert_eq!(deliveries[sender], DeliveryStatus::Pending);
+        }
+
+        // Without a parent, nothing is carried over
+        let third = workspace.record_draw(DrawKind::Draw, None, None, second_reveal, None);
+        assert!(workspace
+            .draw(Some(third))
+            .unwrap()
+            .deliveries
+            .values()
+            .all(|status| *status == DeliveryStatus::Pending));
+        fs::remove_dir_all(&workspace.directory).unwrap();
+    }
+}